@@ -1 +1,4 @@
 export const ZagreusContainerId = "zagreus-container";
+// Has to match the server version and protocol version, the server warns about mismatches.
+export const ZagreusRuntimeVersion = "0.0.9";
+export const ZagreusProtocolVersion = 1;
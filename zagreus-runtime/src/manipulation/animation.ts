@@ -1,30 +1,62 @@
 import {
   AnimationDirection,
   AnimationIterationCount,
+  AnimationLifecycleMessage,
   AnimationSequence,
   AnimationStep,
 } from "../websocket/types";
 import { getInternalZagreusState, InternalZagreusState } from "../runtime";
 import { getZagreusElement } from "../utils";
 
+// end timers of the running sequences that are not part of a queue
+const unqueuedSequenceTimeouts: Record<string, number> = {};
+
 export const applyAnimation = (
   sequenceName: string,
   queueId: string | undefined,
 ): void => {
   const state = getInternalZagreusState();
   const sequence = findAnimationSequence(sequenceName, state);
-  if (sequence) {
-    if (queueId) {
-      let queue = state.animationQueues[queueId];
-      if (!queue) {
-        queue = new AnimationQueue();
-        state.animationQueues[queueId] = queue;
-      }
-      queue.enqueueAnimationSequence(sequence);
-    } else {
-      scheduleAnimationSequence(sequence.steps);
+  if (!sequence) {
+    reportAnimationLifecycle("AnimationCancelled", sequenceName, queueId);
+    return;
+  }
+  if (queueId) {
+    let queue = state.animationQueues[queueId];
+    if (!queue) {
+      queue = new AnimationQueue(queueId);
+      state.animationQueues[queueId] = queue;
     }
+    queue.enqueueAnimationSequence(sequence);
+  } else {
+    executeUnqueuedSequence(sequence);
+  }
+};
+
+const executeUnqueuedSequence = (sequence: AnimationSequence): void => {
+  const runningTimeout = unqueuedSequenceTimeouts[sequence.name];
+  if (runningTimeout !== undefined) {
+    clearTimeout(runningTimeout);
+    reportAnimationLifecycle("AnimationCancelled", sequence.name, undefined);
   }
+  reportAnimationLifecycle("AnimationStarted", sequence.name, undefined);
+  scheduleAnimationSequence(sequence.steps);
+  unqueuedSequenceTimeouts[sequence.name] = window.setTimeout(() => {
+    delete unqueuedSequenceTimeouts[sequence.name];
+    reportAnimationLifecycle("AnimationFinished", sequence.name, undefined);
+  }, getMaxTimeoutFromSequence(sequence));
+};
+
+const reportAnimationLifecycle = (
+  tag: AnimationLifecycleMessage,
+  sequenceName: string,
+  queueId: string | undefined,
+): void => {
+  getInternalZagreusState().websocketSender?.sendAnimationMessage(
+    tag,
+    sequenceName,
+    queueId,
+  );
 };
 
 const scheduleAnimationSequence = (steps: AnimationStep[]): void => {
@@ -117,7 +149,7 @@ export class AnimationQueue {
   readonly queue: AnimationSequence[] = [];
   currentlyExecutingSequence: AnimationSequence | undefined = undefined;
 
-  constructor() {}
+  constructor(private readonly queueId: string) {}
 
   enqueueAnimationSequence(sequence: AnimationSequence) {
     if (!this.currentlyExecutingSequence) {
@@ -139,8 +171,14 @@ export class AnimationQueue {
   private executeSequence(sequence: AnimationSequence) {
     this.currentlyExecutingSequence = sequence;
     const duration = getMaxTimeoutFromSequence(sequence);
+    reportAnimationLifecycle("AnimationStarted", sequence.name, this.queueId);
     scheduleAnimationSequence(sequence.steps);
     setTimeout(() => {
+      reportAnimationLifecycle(
+        "AnimationFinished",
+        sequence.name,
+        this.queueId,
+      );
       this.executeNextSequence();
     }, duration);
   }
@@ -1,5 +1,10 @@
 import { AnimationSequence } from "./websocket/types";
-import { registerAnimations, registerStateListener, setup } from "./setup";
+import {
+  registerAnimations,
+  registerStateListener,
+  sendEvent,
+  setup,
+} from "./setup";
 import { AnimationQueue } from "./manipulation/animation";
 import { WebsocketSender } from "./websocket/websocket-sender";
 
@@ -23,6 +28,10 @@ export interface ZagreusSetupArguments {
   port: string;
   secure: boolean;
   instance: string;
+  // stable name of this client (e.g. "program-left") to target it across reconnects
+  clientName?: string;
+  // tags of this client (e.g. "left-wall") to target groups of clients
+  clientTags?: string[];
   container: ZagreusContainerSetupArguments;
   animationSequences?: AnimationSequence[];
 }
@@ -34,6 +43,7 @@ export interface ZagreusState {
     stateName: string,
     listener: StateChangeListener,
   ) => void;
+  sendEvent: (name: string, payload?: unknown) => void;
   _internal: InternalZagreusState;
 }
 
@@ -47,6 +57,9 @@ export interface InternalZagreusState {
   host: string;
   port: string;
   secure?: boolean;
+  clientName?: string;
+  clientTags: string[];
+  container?: ZagreusContainerSetupArguments;
   websocketSender?: WebsocketSender;
   animationSequences: Record<string, AnimationSequence>;
   animationQueues: Record<string, AnimationQueue>;
@@ -59,12 +72,16 @@ if (!window.zagreus) {
     setup: setup,
     registerStateListener: registerStateListener,
     registerAnimations: registerAnimations,
+    sendEvent: sendEvent,
     _internal: {
       // Placeholders until setup() and installErrorHandler() populate them.
       instance: "",
       host: "",
       port: "",
       secure: false,
+      clientName: undefined,
+      clientTags: [],
+      container: undefined,
       websocketSender: undefined,
       animationSequences: {},
       animationQueues: {},
@@ -37,6 +37,9 @@ export function setup(args: ZagreusSetupArguments) {
   state.host = args.host;
   state.port = args.port;
   state.secure = args.secure;
+  state.clientName = args.clientName;
+  state.clientTags = args.clientTags ?? [];
+  state.container = args.container;
 
   setupContainer(args.container);
   if (args.animationSequences) {
@@ -90,3 +93,10 @@ export function registerStateListener(
   }
   state.states[stateName].changedListeners.push(listener);
 }
+
+export function sendEvent(name: string, payload?: unknown) {
+  getInternalZagreusState().websocketSender?.sendCustomEventMessage(
+    name,
+    payload,
+  );
+}
@@ -7,7 +7,10 @@ import { getInternalZagreusState, getWebsocketProtocol } from "../runtime";
 export function runWebsocket(): void {
   const state = getInternalZagreusState();
   const websocketProtocol = getWebsocketProtocol();
-  const url = `${websocketProtocol}://${state.host}:${state.port}/ws/instance/${state.instance}`;
+  let url = `${websocketProtocol}://${state.host}:${state.port}/ws/instance/${state.instance}`;
+  if (state.clientName) {
+    url += `?name=${encodeURIComponent(state.clientName)}`;
+  }
   const websocket = new TemplateWebsocket(url);
   const websocketSender = new WebsocketSender(websocket);
 
@@ -15,6 +18,7 @@ export function runWebsocket(): void {
   state.websocketSender = websocketSender;
 
   websocket.messageHandler = new WebsocketHandler(websocketSender);
+  websocket.openHandler = () => websocketSender.sendHelloMessage();
 
   websocket.run();
 }
@@ -15,9 +15,23 @@ export type ServerMessage =
   | "ExecuteAnimation"
   | "SetImageSource"
   | "SetCustomVariable"
-  | "SetState";
+  | "SetState"
+  | "Batch";
 
-export type ClientMessage = "LogError" | "StateSet";
+export type ServerCommand = TaggedEnumType<ServerMessage> & { id: number };
+
+export type AnimationLifecycleMessage =
+  | "AnimationStarted"
+  | "AnimationFinished"
+  | "AnimationCancelled";
+
+export type ClientMessage =
+  | "LogError"
+  | "StateSet"
+  | "Ack"
+  | "Hello"
+  | "CustomEvent"
+  | AnimationLifecycleMessage;
 
 export type AssetSource = "template" | "zagreus";
 export type SetTextPayload = { id: string; text: string };
@@ -36,8 +50,24 @@ export type SetCustomVariablePayload = {
   name: string;
   value: string;
 };
+export type BatchPayload = { messages: TaggedEnumType<ServerMessage>[] };
 export type LogErrorPayload = { message: string; stack: string };
 export type StateSetPayload = { name: string; value?: string };
+export type AckPayload = { id: number; ok: boolean; error?: string };
+export type HelloPayload = {
+  runtimeVersion: string;
+  protocolVersion: number;
+  container: { width: number; height: number };
+  userAgent: string;
+  supportedMessages: string[];
+  clientName?: string;
+  clientTags: string[];
+};
+export type CustomEventPayload = { name: string; payload: unknown };
+export type AnimationLifecyclePayload = {
+  sequence: string;
+  queueId?: string;
+};
 
 export interface AnimationSequence {
   name: string;
@@ -1,14 +1,15 @@
 import { WebsocketSender } from "./websocket-sender";
 import {
+  BatchPayload,
   EnumTypeHandler,
   ExecuteAnimationPayload,
   ManipulateClassPayload,
+  ServerCommand,
   ServerMessage,
   SetCustomVariablePayload,
   SetImageSourcePayload,
   SetStatePayload,
   SetTextPayload,
-  TaggedEnumType,
 } from "./types";
 import { setTextOnElement } from "../manipulation/text";
 import { addClassOnElement, removeClassOnElement } from "../manipulation/css";
@@ -54,16 +55,40 @@ const templateMessageHandlers: EnumTypeHandler<ServerMessage, WebsocketSender> =
       );
       sender.sendStateSetMessage(payload.name, value);
     },
+    Batch: (payload: BatchPayload, sender) => {
+      // apply all messages synchronously so that they end up in the same frame
+      const errors: Error[] = [];
+      payload.messages.forEach((message) => {
+        try {
+          templateMessageHandlers[message.tag](message.payload, sender);
+        } catch (error) {
+          errors.push(<Error>error);
+        }
+      });
+      if (errors.length > 0) {
+        throw new Error(errors.map((error) => error.message).join("\n"));
+      }
+    },
   };
 
+export const getSupportedMessages = (): ServerMessage[] =>
+  <ServerMessage[]>Object.keys(templateMessageHandlers);
+
 export class WebsocketHandler {
   constructor(private sender: WebsocketSender) {}
 
   handleMessage(message: string): void {
-    const parsedMessage: TaggedEnumType<ServerMessage> = JSON.parse(message);
-    templateMessageHandlers[parsedMessage.tag](
-      parsedMessage.payload,
-      this.sender,
-    );
+    const parsedMessage: ServerCommand = JSON.parse(message);
+    try {
+      templateMessageHandlers[parsedMessage.tag](
+        parsedMessage.payload,
+        this.sender,
+      );
+    } catch (error) {
+      this.sender.sendAckMessage(parsedMessage.id, <Error>error);
+      getInternalZagreusState().errorReporter(<Error>error);
+      return;
+    }
+    this.sender.sendAckMessage(parsedMessage.id);
   }
 }
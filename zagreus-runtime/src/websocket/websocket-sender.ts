@@ -1,5 +1,17 @@
 import { TemplateWebsocket } from "./template-websocket";
-import { ClientMessage, StateSetPayload, TaggedEnumType } from "./types";
+import {
+  AckPayload,
+  AnimationLifecycleMessage,
+  AnimationLifecyclePayload,
+  ClientMessage,
+  CustomEventPayload,
+  HelloPayload,
+  StateSetPayload,
+  TaggedEnumType,
+} from "./types";
+import { getInternalZagreusState } from "../runtime";
+import { ZagreusProtocolVersion, ZagreusRuntimeVersion } from "../constants";
+import { getSupportedMessages } from "./websocket-handler";
 
 export class WebsocketSender {
   constructor(private websocket: TemplateWebsocket) {}
@@ -24,4 +36,67 @@ export class WebsocketSender {
     };
     this.sendMessage(message);
   }
+
+  sendCustomEventMessage(name: string, payload: unknown): void {
+    const message: TaggedEnumType<ClientMessage, CustomEventPayload> = {
+      tag: "CustomEvent",
+      payload: {
+        name,
+        payload: payload ?? null,
+      },
+    };
+    this.sendMessage(message);
+  }
+
+  sendAckMessage(id: number, error?: Error): void {
+    const message: TaggedEnumType<ClientMessage, AckPayload> = {
+      tag: "Ack",
+      payload: {
+        id,
+        ok: !error,
+        error: error?.message,
+      },
+    };
+    this.sendMessage(message);
+  }
+
+  sendHelloMessage(): void {
+    const state = getInternalZagreusState();
+    const container = state.container;
+    const message: TaggedEnumType<ClientMessage, HelloPayload> = {
+      tag: "Hello",
+      payload: {
+        runtimeVersion: ZagreusRuntimeVersion,
+        protocolVersion: ZagreusProtocolVersion,
+        container: {
+          width: container?.width ?? 0,
+          height: container?.height ?? 0,
+        },
+        userAgent: navigator.userAgent,
+        supportedMessages: getSupportedMessages(),
+        clientName: state.clientName,
+        clientTags: state.clientTags,
+      },
+    };
+    this.sendMessage(message);
+  }
+
+  sendAnimationMessage(
+    tag: AnimationLifecycleMessage,
+    sequence: string,
+    queueId: string | undefined,
+  ): void {
+    // animations that run while disconnected (e.g. on load) are not tracked
+    if (!this.websocket.isOpen()) {
+      return;
+    }
+    const message: TaggedEnumType<ClientMessage, AnimationLifecyclePayload> = {
+      tag,
+      payload: {
+        sequence,
+        queueId,
+      },
+    };
+    this.sendMessage(message);
+  }
 }
@@ -1,9 +1,25 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
+use crate::data::operation::DataOperation;
+use crate::websocket::target::ClientSelector;
+
 pub mod loader;
 
 const DEFAULT_DATA_FOLDER: &str = "data";
 const DEFAULT_SERVER_PORT: u16 = 58180;
+const DEFAULT_HEARTBEAT_INTERVAL_SECONDS: u64 = 10;
+const DEFAULT_HEARTBEAT_TIMEOUT_SECONDS: u64 = 30;
+const DEFAULT_SEND_QUEUE_SIZE: usize = 1000;
+const DEFAULT_TEMPLATE_ERROR_CAPACITY: usize = 100;
+const DEFAULT_WEBHOOK_MAX_RETRIES: u32 = 3;
+const DEFAULT_WEBHOOK_RETRY_DELAY_MILLIS: u64 = 1000;
+const DEFAULT_WEBHOOK_TIMEOUT_MILLIS: u64 = 5000;
+const DEFAULT_MAX_UPLOAD_SIZE_BYTES: usize = 20 * 1024 * 1024;
+const DEFAULT_RENDITION_MAX_WIDTH: u32 = 3840;
+const DEFAULT_RENDITION_MAX_HEIGHT: u32 = 2160;
+const DEFAULT_RENDITION_MAX_SOURCE_DIMENSION: u32 = 16384;
+const DEFAULT_RENDITION_MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
 
 fn get_default_data_folder() -> PathBuf {
     match crate::fs::get_application_folder(crate::APPLICATION_NAME) {
@@ -36,6 +52,100 @@ fn get_default_server_port() -> u16 {
     DEFAULT_SERVER_PORT
 }
 
+fn get_default_persist_instance_data() -> bool {
+    true
+}
+
+fn get_default_heartbeat_interval_seconds() -> u64 {
+    DEFAULT_HEARTBEAT_INTERVAL_SECONDS
+}
+
+fn get_default_heartbeat_timeout_seconds() -> u64 {
+    DEFAULT_HEARTBEAT_TIMEOUT_SECONDS
+}
+
+fn get_default_send_queue_size() -> usize {
+    DEFAULT_SEND_QUEUE_SIZE
+}
+
+fn get_default_overflow_policy() -> OverflowPolicy {
+    OverflowPolicy::DropOldest
+}
+
+fn get_default_overflow_policies() -> HashMap<String, OverflowPolicy> {
+    HashMap::from([
+        ("SetText".to_owned(), OverflowPolicy::Coalesce),
+        ("SetCustomVariable".to_owned(), OverflowPolicy::Coalesce),
+    ])
+}
+
+fn get_default_template_error_capacity() -> usize {
+    DEFAULT_TEMPLATE_ERROR_CAPACITY
+}
+
+fn get_default_webhook_events() -> Vec<String> {
+    [
+        "client-connected",
+        "client-disconnected",
+        "log-error",
+        "state-set",
+        "animation-finished",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn get_default_webhook_max_retries() -> u32 {
+    DEFAULT_WEBHOOK_MAX_RETRIES
+}
+
+fn get_default_webhook_retry_delay_millis() -> u64 {
+    DEFAULT_WEBHOOK_RETRY_DELAY_MILLIS
+}
+
+fn get_default_webhook_timeout_millis() -> u64 {
+    DEFAULT_WEBHOOK_TIMEOUT_MILLIS
+}
+
+fn get_default_max_upload_size_bytes() -> usize {
+    DEFAULT_MAX_UPLOAD_SIZE_BYTES
+}
+
+fn get_default_allowed_media_types() -> Vec<String> {
+    [
+        "image/png",
+        "image/jpeg",
+        "image/gif",
+        "image/webp",
+        "image/avif",
+        "image/svg+xml",
+        "video/mp4",
+        "video/webm",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn get_default_rendition_max_width() -> u32 {
+    DEFAULT_RENDITION_MAX_WIDTH
+}
+
+fn get_default_rendition_max_height() -> u32 {
+    DEFAULT_RENDITION_MAX_HEIGHT
+}
+
+fn get_default_rendition_max_source_dimension() -> u32 {
+    DEFAULT_RENDITION_MAX_SOURCE_DIMENSION
+}
+
+fn get_default_rendition_max_decode_bytes() -> u64 {
+    DEFAULT_RENDITION_MAX_DECODE_BYTES
+}
+
+fn get_default_rendition_widths() -> Vec<u32> {
+    vec![320, 640, 1280, 1920, 3840]
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ZagreusServerConfig {
@@ -43,6 +153,23 @@ pub struct ZagreusServerConfig {
     pub server_port: u16,
     #[serde(default = "get_default_data_folder")]
     pub data_folder: PathBuf,
+    #[serde(default = "get_default_persist_instance_data")]
+    pub persist_instance_data: bool,
+    #[serde(default)]
+    pub heartbeat: HeartbeatConfig,
+    #[serde(default)]
+    pub send_queue: SendQueueConfig,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub event_rules: Vec<EventRuleConfig>,
+    #[serde(default)]
+    pub asset_upload: AssetUploadConfig,
+    #[serde(default)]
+    pub asset_renditions: AssetRenditionConfig,
+    /// Number of distinct template errors kept per client of an instance.
+    #[serde(default = "get_default_template_error_capacity")]
+    pub template_error_capacity: usize,
 }
 
 impl Default for ZagreusServerConfig {
@@ -50,6 +177,193 @@ impl Default for ZagreusServerConfig {
         ZagreusServerConfig {
             server_port: get_default_server_port(),
             data_folder: get_default_data_folder(),
+            persist_instance_data: get_default_persist_instance_data(),
+            heartbeat: HeartbeatConfig::default(),
+            send_queue: SendQueueConfig::default(),
+            webhooks: Vec::new(),
+            event_rules: Vec::new(),
+            asset_upload: AssetUploadConfig::default(),
+            asset_renditions: AssetRenditionConfig::default(),
+            template_error_capacity: get_default_template_error_capacity(),
         }
     }
 }
+
+/// Clients are pinged every interval and disconnected if they did not answer within the
+/// timeout. An interval of 0 disables the heartbeat.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HeartbeatConfig {
+    #[serde(default = "get_default_heartbeat_interval_seconds")]
+    pub interval_seconds: u64,
+    #[serde(default = "get_default_heartbeat_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            interval_seconds: get_default_heartbeat_interval_seconds(),
+            timeout_seconds: get_default_heartbeat_timeout_seconds(),
+        }
+    }
+}
+
+/// Every client has a queue of messages waiting to be sent with the given maximum size. When it
+/// is full, the policy configured for the message kind (e.g. `SetText`) or the default policy
+/// decides what happens. Data replayed to a client that connects is queued in addition and never
+/// dropped.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SendQueueConfig {
+    #[serde(default = "get_default_send_queue_size")]
+    pub size: usize,
+    #[serde(default = "get_default_overflow_policy")]
+    pub default_policy: OverflowPolicy,
+    #[serde(default = "get_default_overflow_policies")]
+    pub policies: HashMap<String, OverflowPolicy>,
+}
+
+impl Default for SendQueueConfig {
+    fn default() -> Self {
+        SendQueueConfig {
+            size: get_default_send_queue_size(),
+            default_policy: get_default_overflow_policy(),
+            policies: get_default_overflow_policies(),
+        }
+    }
+}
+
+/// Uploaded assets larger than the maximum size or with a media type that is not allowed are
+/// rejected. The media type is derived from the file extension and has to match the content.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetUploadConfig {
+    #[serde(default = "get_default_max_upload_size_bytes")]
+    pub max_size_bytes: usize,
+    #[serde(default = "get_default_allowed_media_types")]
+    pub allowed_media_types: Vec<String>,
+}
+
+impl Default for AssetUploadConfig {
+    fn default() -> Self {
+        AssetUploadConfig {
+            max_size_bytes: get_default_max_upload_size_bytes(),
+            allowed_media_types: get_default_allowed_media_types(),
+        }
+    }
+}
+
+/// PNG, JPEG and WebP assets can be requested as renditions that are scaled down to fit the
+/// maximum size, rotated according to their EXIF orientation and encoded again without any
+/// metadata. Renditions are requested with a width (`/assets/{name}?w=640`), which is rounded up
+/// to the next of the configured widths, or with the name of a variant
+/// (`/assets/{name}?variant=thumbnail`). Variants are created when an image is uploaded, all other
+/// renditions when they are requested for the first time.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetRenditionConfig {
+    #[serde(default = "get_default_rendition_max_width")]
+    pub max_width: u32,
+    #[serde(default = "get_default_rendition_max_height")]
+    pub max_height: u32,
+    /// Images wider or higher than this are not rendered.
+    #[serde(default = "get_default_rendition_max_source_dimension")]
+    pub max_source_width: u32,
+    #[serde(default = "get_default_rendition_max_source_dimension")]
+    pub max_source_height: u32,
+    /// Memory the decoder may allocate for an image.
+    #[serde(default = "get_default_rendition_max_decode_bytes")]
+    pub max_decode_bytes: u64,
+    #[serde(default = "get_default_rendition_widths")]
+    pub widths: Vec<u32>,
+    /// Format of renditions that do not specify one.
+    #[serde(default)]
+    pub format: RenditionFormat,
+    #[serde(default)]
+    pub variants: HashMap<String, RenditionVariantConfig>,
+}
+
+impl Default for AssetRenditionConfig {
+    fn default() -> Self {
+        AssetRenditionConfig {
+            max_width: get_default_rendition_max_width(),
+            max_height: get_default_rendition_max_height(),
+            max_source_width: get_default_rendition_max_source_dimension(),
+            max_source_height: get_default_rendition_max_source_dimension(),
+            max_decode_bytes: get_default_rendition_max_decode_bytes(),
+            widths: get_default_rendition_widths(),
+            format: RenditionFormat::default(),
+            variants: HashMap::new(),
+        }
+    }
+}
+
+/// The image is scaled down to fit into width and height, missing ones are limited by the
+/// maximum size of renditions.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RenditionVariantConfig {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: Option<RenditionFormat>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenditionFormat {
+    #[default]
+    Webp,
+    Png,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowPolicy {
+    /// Drop the oldest queued message.
+    DropOldest,
+    /// Replace a queued message for the same element or variable, drop the oldest message if
+    /// there is none.
+    Coalesce,
+    /// Disconnect the client.
+    Disconnect,
+}
+
+/// Server events (e.g. `client-connected`) are posted as JSON to the URL of every webhook whose
+/// filters match. Failed deliveries are retried with a delay that doubles after every attempt.
+/// With a secret, the body is signed with HMAC-SHA256 in the `X-Zagreus-Signature` header.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default = "get_default_webhook_events")]
+    pub events: Vec<String>,
+    /// Instances to deliver events of, all instances if empty.
+    #[serde(default)]
+    pub instances: Vec<String>,
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "get_default_webhook_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "get_default_webhook_retry_delay_millis")]
+    pub retry_delay_millis: u64,
+    #[serde(default = "get_default_webhook_timeout_millis")]
+    pub timeout_millis: u64,
+}
+
+/// Sends data operations to other instances whenever a client sends a custom event with the given
+/// name, e.g. a button press on a tablet instance starts an animation on the program instance.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRuleConfig {
+    pub event: String,
+    /// Instances whose events trigger the rule, all instances if empty.
+    #[serde(default)]
+    pub source_instances: Vec<String>,
+    /// Comma separated instance names or patterns (e.g. `program-*`) to send the operations to.
+    pub instances: String,
+    /// Clients of the target instances to send the operations to, all clients if not set.
+    #[serde(default)]
+    pub client: Option<ClientSelector>,
+    pub operations: Vec<DataOperation>,
+}
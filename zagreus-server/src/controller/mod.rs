@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use crate::websocket::connection::ConnectionInfo;
 use crate::websocket::server::WebsocketServer;
 
 pub struct ServerController {
@@ -11,9 +12,14 @@ impl ServerController {
         ServerController { websocket_server }
     }
 
-    pub async fn add_websocket_client(&self, socket: axum::extract::ws::WebSocket, instance: &str) {
+    pub async fn add_websocket_client(
+        &self,
+        socket: axum::extract::ws::WebSocket,
+        instance: &str,
+        connection_info: ConnectionInfo,
+    ) {
         self.websocket_server
-            .add_client_socket(socket, instance)
+            .add_client_socket(socket, instance, connection_info)
             .await
     }
 }
@@ -0,0 +1,451 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+pub mod rendition;
+mod sniff;
+mod svg;
+pub mod upload;
+
+const ASSET_INDEX_FILE_NAME: &str = "assets.json";
+const ASSET_ALIASES_FILE_NAME: &str = "asset-aliases.json";
+/// Number of earlier assets kept in the history of an alias.
+const MAX_ALIAS_HISTORY: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssetSource {
+    Zagreus,
+    Template,
+}
+
+/// What the server knows about an asset file besides its content.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetMetadata {
+    pub size: u64,
+    pub content_type: String,
+    pub uploaded_at: DateTime<Utc>,
+    /// File name the asset was uploaded with, unknown for files that were added to the assets
+    /// folder directly.
+    pub original_name: Option<String>,
+}
+
+type AssetIndex = BTreeMap<String, AssetMetadata>;
+
+/// A stable name (e.g. `team-home-logo`) for an asset, which can be pointed to another asset
+/// later on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetAlias {
+    pub asset: String,
+    pub updated_at: DateTime<Utc>,
+    /// Assets the alias pointed to earlier, the most recent one last.
+    pub history: Vec<AssetAliasTarget>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetAliasTarget {
+    pub asset: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+type AssetAliases = BTreeMap<String, AssetAlias>;
+
+/// Aliases given as asset of images, mapped to the assets they pointed to when they were
+/// resolved. Messages borrow the assets from here, so it has to outlive them.
+#[derive(Default)]
+pub struct ResolvedAliases {
+    assets: HashMap<String, String>,
+}
+
+impl ResolvedAliases {
+    /// Returns the asset to show and the alias it was resolved from. Assets that are no alias
+    /// are returned as they are.
+    pub fn resolve<'a>(
+        &'a self,
+        asset: &'a str,
+        asset_source: AssetSource,
+    ) -> (&'a str, Option<&'a str>) {
+        match self.assets.get_key_value(asset) {
+            Some((alias, alias_asset)) if asset_source == AssetSource::Zagreus => {
+                (alias_asset, Some(alias))
+            }
+            _ => (asset, None),
+        }
+    }
+}
+
+/// Keeps an index of the files in the assets folder and the aliases pointing to them in memory
+/// and stores both as JSON files next to the assets folder.
+pub struct AssetStore {
+    assets_folder: PathBuf,
+    index_path: PathBuf,
+    index: RwLock<AssetIndex>,
+    aliases_path: PathBuf,
+    aliases: RwLock<AssetAliases>,
+}
+
+impl AssetStore {
+    pub fn load(data_folder: &Path) -> anyhow::Result<AssetStore> {
+        let assets_folder = crate::fs::get_assets_folder(data_folder)?;
+        let index_path = data_folder.join(ASSET_INDEX_FILE_NAME);
+        let mut index: AssetIndex = load_json_file(&index_path, "asset index");
+        let aliases_path = data_folder.join(ASSET_ALIASES_FILE_NAME);
+        let aliases: AssetAliases = load_json_file(&aliases_path, "asset aliases");
+
+        // files might have been added or removed while the server was not running
+        let mut file_names = BTreeSet::new();
+        let entries = std::fs::read_dir(&assets_folder)
+            .with_context(|| format!("Could not read assets folder {assets_folder:?}"))?;
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let Some(name) = entry.file_name().to_str().map(String::from) else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            index.entry(name.clone()).or_insert_with(|| AssetMetadata {
+                size: metadata.len(),
+                content_type: guess_content_type(&name),
+                uploaded_at: metadata
+                    .modified()
+                    .map(DateTime::from)
+                    .unwrap_or_else(|_| Utc::now()),
+                original_name: None,
+            });
+            file_names.insert(name);
+        }
+        index.retain(|name, _| file_names.contains(name));
+
+        Ok(AssetStore {
+            assets_folder,
+            index_path,
+            index: RwLock::new(index),
+            aliases_path,
+            aliases: RwLock::new(aliases),
+        })
+    }
+
+    pub async fn get_assets(&self) -> Vec<(String, AssetMetadata)> {
+        self.index
+            .read()
+            .await
+            .iter()
+            .map(|(name, metadata)| (name.clone(), metadata.clone()))
+            .collect()
+    }
+
+    pub async fn get_asset(&self, name: &str) -> Option<AssetMetadata> {
+        self.index.read().await.get(name).cloned()
+    }
+
+    /// Records an asset file that was written to the assets folder. Uploading the same content
+    /// again replaces the metadata of the earlier upload.
+    pub async fn add_asset(
+        &self,
+        name: &str,
+        original_name: &str,
+        size: u64,
+        content_type: &str,
+    ) -> anyhow::Result<()> {
+        let mut index = self.index.write().await;
+        let mut updated_index = index.clone();
+        updated_index.insert(
+            name.to_string(),
+            AssetMetadata {
+                size,
+                content_type: content_type.to_string(),
+                uploaded_at: Utc::now(),
+                original_name: Some(original_name.to_string()),
+            },
+        );
+        self.store_index(&updated_index).await?;
+        *index = updated_index;
+        Ok(())
+    }
+
+    /// Deletes the asset file and its renditions, returns whether an asset with the given name
+    /// existed. The asset is removed from the index first, a file that could not be deleted is
+    /// indexed again on the next start.
+    pub async fn delete_asset(&self, name: &str) -> anyhow::Result<bool> {
+        let mut index = self.index.write().await;
+        let mut updated_index = index.clone();
+        if updated_index.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.store_index(&updated_index).await?;
+        *index = updated_index;
+        match tokio::fs::remove_file(self.assets_folder.join(name)).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).with_context(|| format!("Could not delete asset {name}")),
+        }
+        rendition::delete_renditions(&self.assets_folder, name).await?;
+        Ok(true)
+    }
+
+    async fn store_index(&self, index: &AssetIndex) -> anyhow::Result<()> {
+        let serialized_index = serde_json::to_vec_pretty(index)?;
+        crate::fs::write_file_atomically(&self.index_path, &serialized_index).await
+    }
+
+    pub async fn get_aliases(&self) -> Vec<(String, AssetAlias)> {
+        self.aliases
+            .read()
+            .await
+            .iter()
+            .map(|(name, alias)| (name.clone(), alias.clone()))
+            .collect()
+    }
+
+    pub async fn get_alias(&self, name: &str) -> Option<AssetAlias> {
+        self.aliases.read().await.get(name).cloned()
+    }
+
+    /// Names of the aliases currently pointing to the asset.
+    pub async fn get_aliases_of_asset(&self, asset: &str) -> Vec<String> {
+        self.aliases
+            .read()
+            .await
+            .iter()
+            .filter(|(_, alias)| alias.asset == asset)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Looks up which of the given assets are aliases and which assets they point to.
+    pub async fn resolve_aliases<'a>(
+        &self,
+        assets: impl IntoIterator<Item = &'a str>,
+    ) -> ResolvedAliases {
+        let aliases = self.aliases.read().await;
+        ResolvedAliases {
+            assets: assets
+                .into_iter()
+                .filter_map(|asset| {
+                    aliases
+                        .get(asset)
+                        .map(|alias| (asset.to_string(), alias.asset.clone()))
+                })
+                .collect(),
+        }
+    }
+
+    /// Points the alias to the asset, the asset it pointed to before is kept in its history.
+    /// Returns whether the alias pointed to a different asset before.
+    pub async fn set_alias(&self, name: &str, asset: &str) -> anyhow::Result<bool> {
+        let mut aliases = self.aliases.write().await;
+        let mut updated_aliases = aliases.clone();
+        let now = Utc::now();
+        let changed = match updated_aliases.get_mut(name) {
+            Some(alias) if alias.asset == asset => false,
+            Some(alias) => {
+                alias.history.push(AssetAliasTarget {
+                    asset: std::mem::replace(&mut alias.asset, asset.to_string()),
+                    updated_at: alias.updated_at,
+                });
+                if alias.history.len() > MAX_ALIAS_HISTORY {
+                    alias.history.remove(0);
+                }
+                alias.updated_at = now;
+                true
+            }
+            None => {
+                updated_aliases.insert(
+                    name.to_string(),
+                    AssetAlias {
+                        asset: asset.to_string(),
+                        updated_at: now,
+                        history: Vec::new(),
+                    },
+                );
+                false
+            }
+        };
+        self.store_aliases(&updated_aliases).await?;
+        *aliases = updated_aliases;
+        Ok(changed)
+    }
+
+    /// Returns whether an alias with the given name existed.
+    pub async fn delete_alias(&self, name: &str) -> anyhow::Result<bool> {
+        let mut aliases = self.aliases.write().await;
+        let mut updated_aliases = aliases.clone();
+        if updated_aliases.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.store_aliases(&updated_aliases).await?;
+        *aliases = updated_aliases;
+        Ok(true)
+    }
+
+    async fn store_aliases(&self, aliases: &AssetAliases) -> anyhow::Result<()> {
+        let serialized_aliases = serde_json::to_vec_pretty(aliases)?;
+        crate::fs::write_file_atomically(&self.aliases_path, &serialized_aliases).await
+    }
+}
+
+/// Aliases are short identifiers like `team-home-logo`. They must not contain dots so that they
+/// cannot be mistaken for asset names, which always have an extension.
+pub fn validate_alias(alias: &str) -> Result<(), String> {
+    if alias.is_empty() {
+        Err(String::from("Alias must not be empty."))
+    } else if !alias
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Err(format!(
+            "Alias '{alias}' may only contain letters, digits, dashes and underscores."
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn guess_content_type(name: &str) -> String {
+    mime_guess::from_path(name)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_string()
+}
+
+fn load_json_file<T: DeserializeOwned + Default>(path: &Path, description: &str) -> T {
+    if !path.exists() {
+        return T::default();
+    }
+    std::fs::read_to_string(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| Ok(serde_json::from_str(&contents)?))
+        .unwrap_or_else(|err| {
+            error!("Could not load {} from {:?}: {:#}.", description, path, err);
+            T::default()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_index_assets() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let assets_folder = crate::fs::get_assets_folder(&path).unwrap();
+        std::fs::write(assets_folder.join("copied.png"), b"png").unwrap();
+        std::fs::write(assets_folder.join("uploaded.svg"), b"<svg/>").unwrap();
+
+        let store = AssetStore::load(&path).unwrap();
+        store
+            .add_asset("uploaded.svg", "logo.svg", 6, "image/svg+xml")
+            .await
+            .unwrap();
+        let copied = store.get_asset("copied.png").await.unwrap();
+        assert_eq!(3, copied.size);
+        assert_eq!("image/png", copied.content_type);
+        assert_eq!(None, copied.original_name);
+
+        std::fs::remove_file(assets_folder.join("copied.png")).unwrap();
+        let reloaded_store = AssetStore::load(&path).unwrap();
+        let assets = reloaded_store.get_assets().await;
+        assert_eq!(1, assets.len());
+        assert_eq!("uploaded.svg", assets[0].0);
+        assert_eq!("image/svg+xml", assets[0].1.content_type);
+        assert_eq!(Some(String::from("logo.svg")), assets[0].1.original_name);
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_repoint_alias() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let store = AssetStore::load(&path).unwrap();
+
+        assert!(!store.set_alias("home-logo", "a.png").await.unwrap());
+        assert!(!store.set_alias("home-logo", "a.png").await.unwrap());
+        assert!(store.set_alias("home-logo", "b.png").await.unwrap());
+        assert_eq!(vec!["home-logo"], store.get_aliases_of_asset("b.png").await);
+        assert!(store.get_aliases_of_asset("a.png").await.is_empty());
+
+        let alias = AssetStore::load(&path)
+            .unwrap()
+            .get_alias("home-logo")
+            .await
+            .unwrap();
+        assert_eq!("b.png", alias.asset);
+        let history: Vec<&str> = alias
+            .history
+            .iter()
+            .map(|target| target.asset.as_str())
+            .collect();
+        assert_eq!(vec!["a.png"], history);
+
+        assert!(store.delete_alias("home-logo").await.unwrap());
+        assert!(!store.delete_alias("home-logo").await.unwrap());
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_alias() {
+        assert!(validate_alias("team-home_logo2").is_ok());
+        assert!(validate_alias("").is_err());
+        assert!(validate_alias("logo.png").is_err());
+        assert!(validate_alias("../logo").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_asset() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let assets_folder = crate::fs::get_assets_folder(&path).unwrap();
+        std::fs::write(assets_folder.join("logo.png"), b"png").unwrap();
+        let store = AssetStore::load(&path).unwrap();
+
+        assert!(store.delete_asset("logo.png").await.unwrap());
+        assert!(!store.delete_asset("logo.png").await.unwrap());
+        assert!(!assets_folder.join("logo.png").exists());
+        assert!(
+            AssetStore::load(&path)
+                .unwrap()
+                .get_assets()
+                .await
+                .is_empty()
+        );
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keep_index_and_aliases_if_storing_fails() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let data_folder = path.join("data");
+        let assets_folder = crate::fs::get_assets_folder(&data_folder).unwrap();
+        std::fs::write(assets_folder.join("logo.png"), b"png").unwrap();
+        let store = AssetStore::load(&data_folder).unwrap();
+        store.set_alias("logo", "logo.png").await.unwrap();
+        // the index and alias files can no longer be written
+        std::fs::remove_dir_all(&data_folder).unwrap();
+
+        assert!(
+            store
+                .add_asset("other.png", "other.png", 3, "image/png")
+                .await
+                .is_err()
+        );
+        assert!(store.delete_asset("logo.png").await.is_err());
+        assert!(store.set_alias("logo", "other.png").await.is_err());
+        assert!(store.delete_alias("logo").await.is_err());
+        assert!(store.get_asset("other.png").await.is_none());
+        assert!(store.get_asset("logo.png").await.is_some());
+        assert_eq!("logo.png", store.get_alias("logo").await.unwrap().asset);
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use tokio::sync::Mutex;
+
+use crate::config::{AssetRenditionConfig, RenditionFormat};
+
+/// Folder in the assets folder that caches the renditions, they are served from
+/// `/assets/renditions/{file}`.
+pub const RENDITIONS_FOLDER_NAME: &str = "renditions";
+const RENDERABLE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+
+/// The image is scaled down to fit into width and height and encoded in the format.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RenditionSpec {
+    pub width: u32,
+    pub height: u32,
+    pub format: RenditionFormat,
+}
+
+impl RenditionSpec {
+    /// Assets are named after their content, so a cached rendition never becomes outdated.
+    fn get_file_name(&self, asset_name: &str) -> String {
+        format!(
+            "{asset_name}.{}x{}.{}",
+            self.width,
+            self.height,
+            get_image_format(self.format).extensions_str()[0]
+        )
+    }
+}
+
+pub struct RenditionCache {
+    assets_folder: PathBuf,
+    renditions_folder: PathBuf,
+    config: AssetRenditionConfig,
+    /// Renditions that are being created, so that concurrent requests for the same rendition
+    /// wait for the first one instead of decoding the image again.
+    pending_renditions: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl RenditionCache {
+    pub fn new(assets_folder: &Path, config: &AssetRenditionConfig) -> anyhow::Result<Self> {
+        let renditions_folder = assets_folder.join(RENDITIONS_FOLDER_NAME);
+        std::fs::create_dir_all(&renditions_folder)
+            .with_context(|| format!("Could not create renditions folder {renditions_folder:?}"))?;
+        let mut config = config.clone();
+        config.widths.sort_unstable();
+        Ok(RenditionCache {
+            assets_folder: assets_folder.to_owned(),
+            renditions_folder,
+            config,
+            pending_renditions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The width is rounded up to the next configured width, so that only a few renditions are
+    /// cached per asset. Wider requests get the widest rendition.
+    pub fn get_spec_for_width(
+        &self,
+        width: u32,
+        format: Option<RenditionFormat>,
+    ) -> Option<RenditionSpec> {
+        let widths = &self.config.widths;
+        let width = widths
+            .iter()
+            .find(|configured_width| **configured_width >= width)
+            .or(widths.last())?;
+        Some(RenditionSpec {
+            width: (*width).min(self.config.max_width),
+            height: self.config.max_height,
+            format: format.unwrap_or(self.config.format),
+        })
+    }
+
+    pub fn get_spec_for_variant(
+        &self,
+        variant: &str,
+        format: Option<RenditionFormat>,
+    ) -> Option<RenditionSpec> {
+        let variant = self.config.variants.get(variant)?;
+        Some(RenditionSpec {
+            width: variant.width.map_or(self.config.max_width, |width| {
+                width.min(self.config.max_width)
+            }),
+            height: variant.height.map_or(self.config.max_height, |height| {
+                height.min(self.config.max_height)
+            }),
+            format: format.or(variant.format).unwrap_or(self.config.format),
+        })
+    }
+
+    /// Returns the file name of the rendition in the renditions folder and creates it if it is
+    /// not cached yet. Returns `None` if there is no such asset.
+    pub async fn get_rendition(
+        &self,
+        asset_name: &str,
+        spec: RenditionSpec,
+    ) -> anyhow::Result<Option<String>> {
+        let file_name = spec.get_file_name(asset_name);
+        let path = self.renditions_folder.join(&file_name);
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(Some(file_name));
+        }
+
+        let pending_rendition = self
+            .pending_renditions
+            .lock()
+            .await
+            .entry(file_name.clone())
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = pending_rendition.lock().await;
+            self.create_rendition(asset_name, &file_name, &path, spec)
+                .await
+        };
+        let mut pending_renditions = self.pending_renditions.lock().await;
+        // only the map and this request still hold it if nobody else is waiting
+        if Arc::strong_count(&pending_rendition) == 2 {
+            pending_renditions.remove(&file_name);
+        }
+        result
+    }
+
+    async fn create_rendition(
+        &self,
+        asset_name: &str,
+        file_name: &str,
+        path: &Path,
+        spec: RenditionSpec,
+    ) -> anyhow::Result<Option<String>> {
+        // an earlier request might have created it in the meantime
+        if tokio::fs::try_exists(path).await? {
+            return Ok(Some(file_name.to_owned()));
+        }
+        let content = match tokio::fs::read(self.assets_folder.join(asset_name)).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Could not read asset {asset_name}"));
+            }
+        };
+        let limits = self.get_limits();
+        let rendition = tokio::task::spawn_blocking(move || render(&content, spec, limits))
+            .await?
+            .with_context(|| format!("Could not render asset {asset_name}"))?;
+        crate::fs::write_file_atomically(path, &rendition).await?;
+        debug!("Created rendition {} of asset {}.", file_name, asset_name);
+        Ok(Some(file_name.to_owned()))
+    }
+
+    fn get_limits(&self) -> Limits {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(self.config.max_source_width);
+        limits.max_image_height = Some(self.config.max_source_height);
+        limits.max_alloc = Some(self.config.max_decode_bytes);
+        limits
+    }
+
+    /// Creates all configured variants of an image, returns the names of the created variants.
+    pub async fn create_variants(&self, asset_name: &str) -> Vec<String> {
+        if !is_renderable(asset_name) {
+            return Vec::new();
+        }
+        let mut variants: Vec<&String> = self.config.variants.keys().collect();
+        variants.sort();
+        let mut created_variants = Vec::new();
+        for variant in variants {
+            let Some(spec) = self.get_spec_for_variant(variant, None) else {
+                continue;
+            };
+            match self.get_rendition(asset_name, spec).await {
+                Ok(Some(_)) => created_variants.push(variant.clone()),
+                Ok(None) => {}
+                Err(err) => error!(
+                    "Could not create variant {} of asset {}: {:#}.",
+                    variant, asset_name, err
+                ),
+            }
+        }
+        created_variants
+    }
+}
+
+/// Only raster images that can be decoded are rendered, other assets are served as they are.
+pub fn is_renderable(asset_name: &str) -> bool {
+    Path::new(asset_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            RENDERABLE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
+
+/// Deletes the cached renditions of an asset.
+pub async fn delete_renditions(assets_folder: &Path, asset_name: &str) -> anyhow::Result<()> {
+    let mut entries = match tokio::fs::read_dir(assets_folder.join(RENDITIONS_FOLDER_NAME)).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).context("Could not read renditions folder"),
+    };
+    let prefix = format!("{asset_name}.");
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name();
+        // the rest is the size and extension, longer names belong to other assets
+        let is_rendition = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&prefix))
+            .is_some_and(|rest| rest.split('.').count() == 2);
+        if is_rendition {
+            tokio::fs::remove_file(entry.path())
+                .await
+                .with_context(|| format!("Could not delete rendition {file_name:?}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the image, rotates it according to its EXIF orientation and scales it down if it is
+/// larger than the spec. Encoding it again drops all metadata.
+fn render(content: &[u8], spec: RenditionSpec, limits: Limits) -> anyhow::Result<Vec<u8>> {
+    let mut reader = ImageReader::new(Cursor::new(content)).with_guessed_format()?;
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    if image.width() > spec.width || image.height() > spec.height {
+        image = image.resize(spec.width, spec.height, FilterType::Lanczos3);
+    }
+    let mut rendition = Cursor::new(Vec::new());
+    image.write_to(&mut rendition, get_image_format(spec.format))?;
+    Ok(rendition.into_inner())
+}
+
+fn get_image_format(format: RenditionFormat) -> ImageFormat {
+    match format {
+        RenditionFormat::Webp => ImageFormat::WebP,
+        RenditionFormat::Png => ImageFormat::Png,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RenditionVariantConfig;
+    use image::codecs::png::PngEncoder;
+    use image::{ImageEncoder, RgbImage};
+
+    // little endian TIFF header with a single IFD entry: orientation (0x0112) = 6 (rotate 90°)
+    const EXIF_ROTATE_90: [u8; 26] = [
+        0x49, 0x49, 0x2a, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x01, 0x03, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn create_png(width: u32, height: u32, exif: Option<&[u8]>) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, _| image::Rgb([(x % 256) as u8, 0, 0]));
+        let mut content = Vec::new();
+        let mut encoder = PngEncoder::new(&mut content);
+        if let Some(exif) = exif {
+            encoder.set_exif_metadata(exif.to_vec()).unwrap();
+        }
+        encoder
+            .write_image(
+                image.as_raw(),
+                width,
+                height,
+                image::ExtendedColorType::Rgb8,
+            )
+            .unwrap();
+        content
+    }
+
+    fn get_config() -> AssetRenditionConfig {
+        AssetRenditionConfig {
+            variants: [(
+                String::from("thumbnail"),
+                RenditionVariantConfig {
+                    width: Some(100),
+                    height: Some(100),
+                    format: Some(RenditionFormat::Png),
+                },
+            )]
+            .into(),
+            ..AssetRenditionConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_scale_down_to_fit() {
+        let spec = RenditionSpec {
+            width: 100,
+            height: 100,
+            format: RenditionFormat::Webp,
+        };
+        let rendition = render(&create_png(400, 200, None), spec, Limits::default()).unwrap();
+
+        let image = image::load_from_memory(&rendition).unwrap();
+        assert_eq!(
+            Ok(ImageFormat::WebP),
+            image::guess_format(&rendition).map_err(|_| ())
+        );
+        assert_eq!((100, 50), (image.width(), image.height()));
+    }
+
+    #[test]
+    fn test_do_not_scale_up() {
+        let spec = RenditionSpec {
+            width: 100,
+            height: 100,
+            format: RenditionFormat::Png,
+        };
+        let rendition = render(&create_png(40, 20, None), spec, Limits::default()).unwrap();
+
+        let image = image::load_from_memory(&rendition).unwrap();
+        assert_eq!((40, 20), (image.width(), image.height()));
+    }
+
+    #[test]
+    fn test_reject_images_exceeding_limits() {
+        let spec = RenditionSpec {
+            width: 100,
+            height: 100,
+            format: RenditionFormat::Png,
+        };
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(200);
+
+        assert!(render(&create_png(400, 20, None), spec, limits).is_err());
+    }
+
+    #[test]
+    fn test_apply_orientation_and_strip_exif() {
+        let spec = RenditionSpec {
+            width: 100,
+            height: 100,
+            format: RenditionFormat::Png,
+        };
+        let rendition = render(
+            &create_png(40, 20, Some(&EXIF_ROTATE_90)),
+            spec,
+            Limits::default(),
+        )
+        .unwrap();
+
+        let mut decoder = ImageReader::new(Cursor::new(&rendition))
+            .with_guessed_format()
+            .unwrap()
+            .into_decoder()
+            .unwrap();
+        assert_eq!(None, decoder.exif_metadata().unwrap());
+        assert_eq!((20, 40), decoder.dimensions());
+    }
+
+    #[test]
+    fn test_get_spec() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let cache = RenditionCache::new(&path, &get_config()).unwrap();
+
+        let spec = cache.get_spec_for_width(500, None).unwrap();
+        assert_eq!(640, spec.width);
+        assert_eq!(2160, spec.height);
+        assert_eq!(RenditionFormat::Webp, spec.format);
+        let spec = cache
+            .get_spec_for_width(5000, Some(RenditionFormat::Png))
+            .unwrap();
+        assert_eq!(3840, spec.width);
+        assert_eq!(RenditionFormat::Png, spec.format);
+        assert_eq!(
+            Some(RenditionSpec {
+                width: 100,
+                height: 100,
+                format: RenditionFormat::Png,
+            }),
+            cache.get_spec_for_variant("thumbnail", None)
+        );
+        assert_eq!(None, cache.get_spec_for_variant("poster", None));
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+
+    #[test]
+    fn test_is_renderable() {
+        assert!(is_renderable("logo.png"));
+        assert!(is_renderable("photo.JPG"));
+        assert!(!is_renderable("logo.svg"));
+        assert!(!is_renderable("clip.mp4"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_and_delete_renditions() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        std::fs::write(path.join("photo.png"), create_png(400, 200, None)).unwrap();
+        std::fs::write(path.join("photo.png.jpg"), b"other asset").unwrap();
+        let cache = RenditionCache::new(&path, &get_config()).unwrap();
+        let renditions_folder = path.join(RENDITIONS_FOLDER_NAME);
+        std::fs::write(
+            renditions_folder.join("photo.png.jpg.100x100.png"),
+            b"rendition of other asset",
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![String::from("thumbnail")],
+            cache.create_variants("photo.png").await
+        );
+        let spec = cache.get_spec_for_width(320, None).unwrap();
+        let file_name = cache.get_rendition("photo.png", spec).await.unwrap();
+        assert_eq!(Some(String::from("photo.png.320x2160.webp")), file_name);
+        assert!(renditions_folder.join("photo.png.100x100.png").exists());
+        assert!(renditions_folder.join("photo.png.320x2160.webp").exists());
+        assert_eq!(
+            None,
+            cache.get_rendition("missing.png", spec).await.unwrap()
+        );
+
+        delete_renditions(&path, "photo.png").await.unwrap();
+        assert!(!renditions_folder.join("photo.png.100x100.png").exists());
+        assert!(!renditions_folder.join("photo.png.320x2160.webp").exists());
+        assert!(renditions_folder.join("photo.png.jpg.100x100.png").exists());
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_rendition_once_for_concurrent_requests() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        std::fs::write(path.join("photo.png"), create_png(400, 200, None)).unwrap();
+        let cache = RenditionCache::new(&path, &get_config()).unwrap();
+        let spec = cache.get_spec_for_width(320, None).unwrap();
+
+        let file_names =
+            futures::future::join_all((0..4).map(|_| cache.get_rendition("photo.png", spec))).await;
+
+        for file_name in file_names {
+            assert_eq!(
+                Some(String::from("photo.png.320x2160.webp")),
+                file_name.unwrap()
+            );
+        }
+        assert!(cache.pending_renditions.lock().await.is_empty());
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+/// Number of leading bytes searched for the root element of an SVG image.
+const SVG_SNIFF_LENGTH: usize = 1024;
+
+/// Media types that can be recognised by their content, besides the ISO base media file formats.
+const SNIFFABLE_MEDIA_TYPES: [&str; 9] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/x-icon",
+    "image/svg+xml",
+    "video/webm",
+    "font/woff2",
+];
+/// Media types of the ISO base media file format (MP4 and related formats). They all start with
+/// an `ftyp` box, but its brand does not always tell them apart (e.g. `isom` is used by video and
+/// audio files alike), so they are treated as one family.
+const ISO_BMFF_MEDIA_TYPES: [&str; 14] = [
+    "video/mp4",
+    "video/x-m4v",
+    "video/quicktime",
+    "video/3gpp",
+    "video/3gpp2",
+    "video/x-f4v",
+    "audio/mp4",
+    "audio/m4a",
+    "audio/m4b",
+    "image/avif",
+    "image/avif-sequence",
+    "image/heic",
+    "image/heif",
+    "image/heif-sequence",
+];
+
+/// Detects the media type of the content from its leading bytes (magic bytes). Returns nothing
+/// if the format is not recognised.
+pub fn sniff_media_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if data.get(4..8) == Some(b"ftyp") {
+        data.get(8..12).map(get_iso_bmff_media_type)
+    } else if data.starts_with(b"\x1a\x45\xdf\xa3") {
+        Some("video/webm")
+    } else if data.starts_with(b"wOF2") {
+        Some("font/woff2")
+    } else if data.starts_with(b"BM") && data.len() > 14 {
+        Some("image/bmp")
+    } else if data.starts_with(b"\x00\x00\x01\x00") {
+        Some("image/x-icon")
+    } else if is_svg(data) {
+        Some("image/svg+xml")
+    } else {
+        None
+    }
+}
+
+/// Whether content of this media type is always recognised by [`sniff_media_type`].
+pub fn is_sniffable(media_type: &str) -> bool {
+    SNIFFABLE_MEDIA_TYPES.contains(&media_type) || ISO_BMFF_MEDIA_TYPES.contains(&media_type)
+}
+
+/// Whether content detected by [`sniff_media_type`] may be stored as the given media type.
+pub fn matches_media_type(detected: &str, media_type: &str) -> bool {
+    detected == media_type
+        || (ISO_BMFF_MEDIA_TYPES.contains(&detected) && ISO_BMFF_MEDIA_TYPES.contains(&media_type))
+}
+
+/// Maps the major brand of the `ftyp` box to a media type, generic brands like `isom` or `mp42`
+/// are reported as MP4 video.
+fn get_iso_bmff_media_type(brand: &[u8]) -> &'static str {
+    match brand {
+        b"avif" => "image/avif",
+        b"avis" => "image/avif-sequence",
+        b"heic" | b"heix" | b"heim" | b"heis" => "image/heic",
+        b"mif1" => "image/heif",
+        b"msf1" | b"hevc" | b"hevx" => "image/heif-sequence",
+        b"qt  " => "video/quicktime",
+        b"M4A " | b"M4B " | b"M4P " => "audio/mp4",
+        b"M4V " | b"M4VH" | b"M4VP" => "video/x-m4v",
+        [b'3', b'g', b'p', _] => "video/3gpp",
+        [b'3', b'g', b'2', _] => "video/3gpp2",
+        _ => "video/mp4",
+    }
+}
+
+fn is_svg(data: &[u8]) -> bool {
+    let start = &data[..data.len().min(SVG_SNIFF_LENGTH)];
+    let start = start.strip_prefix(b"\xef\xbb\xbf").unwrap_or(start);
+    // the XML declaration, comments and a doctype might come before the root element
+    let Some(first) = start.iter().position(|b| !b.is_ascii_whitespace()) else {
+        return false;
+    };
+    start[first] == b'<'
+        && start
+            .windows(4)
+            .any(|window| window.eq_ignore_ascii_case(b"<svg"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_media_type() {
+        assert_eq!(
+            Some("image/png"),
+            sniff_media_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")
+        );
+        assert_eq!(Some("image/jpeg"), sniff_media_type(b"\xff\xd8\xff\xe0"));
+        assert_eq!(Some("image/gif"), sniff_media_type(b"GIF89a\x01\0"));
+        assert_eq!(
+            Some("image/webp"),
+            sniff_media_type(b"RIFF\x24\0\0\0WEBPVP8 ")
+        );
+        assert_eq!(
+            Some("video/mp4"),
+            sniff_media_type(b"\0\0\0\x20ftypisom\0\0\x02\0")
+        );
+        assert_eq!(
+            Some("image/avif"),
+            sniff_media_type(b"\0\0\0\x1cftypavif\0\0\0\0")
+        );
+        assert_eq!(
+            Some("image/heic"),
+            sniff_media_type(b"\0\0\0\x18ftypheic\0\0\0\0")
+        );
+        assert_eq!(
+            Some("audio/mp4"),
+            sniff_media_type(b"\0\0\0\x20ftypM4A \0\0\0\0")
+        );
+        assert_eq!(
+            Some("image/svg+xml"),
+            sniff_media_type(
+                b"\xef\xbb\xbf<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>"
+            )
+        );
+        assert_eq!(None, sniff_media_type(b"hello <svg>"));
+        assert_eq!(None, sniff_media_type(b"<html></html>"));
+        assert_eq!(None, sniff_media_type(b""));
+    }
+
+    #[test]
+    fn test_match_iso_bmff_family() {
+        assert!(matches_media_type("video/mp4", "audio/m4a"));
+        assert!(matches_media_type("video/mp4", "video/x-m4v"));
+        assert!(matches_media_type("image/heic", "image/heic"));
+        assert!(!matches_media_type("video/mp4", "video/webm"));
+        assert!(!matches_media_type("image/png", "image/avif"));
+    }
+
+    #[test]
+    fn test_sniffable_types_match_extensions() {
+        for extension in [
+            "png", "jpg", "gif", "webp", "svg", "mp4", "m4a", "m4v", "mov", "heic", "avif", "webm",
+            "ico",
+        ] {
+            let media_type = mime_guess::from_ext(extension).first().unwrap();
+            assert!(
+                is_sniffable(media_type.essence_str()),
+                "{extension} is not sniffable"
+            );
+        }
+    }
+}
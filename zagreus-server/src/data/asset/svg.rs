@@ -0,0 +1,379 @@
+/// Elements that are removed together with their content as they can run scripts or embed other
+/// documents.
+const REMOVED_ELEMENTS: [&str; 5] = ["script", "foreignobject", "iframe", "embed", "object"];
+/// Attribute values starting with one of these schemes run scripts when used as links.
+const SCRIPT_SCHEMES: [&str; 3] = ["javascript:", "vbscript:", "data:text/html"];
+/// Elements that change attributes of their parent over time. They are removed if they change a
+/// link, as the link could become a script after sanitizing.
+const ANIMATION_ELEMENTS: [&str; 5] = [
+    "animate",
+    "set",
+    "animatemotion",
+    "animatetransform",
+    "animatecolor",
+];
+
+/// Removes scripts, event handler attributes (e.g. `onload`), script links and animations of links
+/// from an SVG image so that it cannot run code when a template embeds it. Doctypes and
+/// processing instructions other than the XML declaration are removed as well, everything else
+/// is kept as it is.
+pub fn sanitize_svg(svg: &str) -> Result<String, String> {
+    let mut sanitized = String::with_capacity(svg.len());
+    let mut rest = svg;
+    // depth of nested elements within a removed element
+    let mut removed_depth = 0usize;
+
+    while let Some(start) = rest.find('<') {
+        if removed_depth == 0 {
+            sanitized.push_str(&rest[..start]);
+        }
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            let end = find_end(rest, "-->", "comment")?;
+            if removed_depth == 0 {
+                sanitized.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+        } else if rest.starts_with("<![CDATA[") {
+            let end = find_end(rest, "]]>", "CDATA section")?;
+            if removed_depth == 0 {
+                sanitized.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+        } else if rest.starts_with("<!") {
+            rest = &rest[find_doctype_end(rest)?..];
+        } else if rest.starts_with("<?") {
+            let end = find_end(rest, "?>", "processing instruction")?;
+            if removed_depth == 0 && rest.starts_with("<?xml ") {
+                sanitized.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+        } else if rest.starts_with("</") {
+            let end = find_end(rest, ">", "end tag")?;
+            if removed_depth == 0 {
+                sanitized.push_str(&rest[..end]);
+            } else {
+                removed_depth -= 1;
+            }
+            rest = &rest[end..];
+        } else {
+            let tag = parse_start_tag(rest)?;
+            rest = &rest[tag.length..];
+            let removed = removed_depth > 0 || is_removed_element(&tag);
+            if tag.self_closing {
+                if !removed {
+                    push_tag(&mut sanitized, &tag);
+                }
+            } else if get_local_name(tag.name).eq_ignore_ascii_case("script") {
+                // scripts may contain '<' which must not be read as tags
+                rest = &rest[find_script_end(rest)?..];
+            } else if removed {
+                removed_depth += 1;
+            } else {
+                push_tag(&mut sanitized, &tag);
+            }
+        }
+    }
+    if removed_depth > 0 {
+        return Err(String::from("SVG image ends within a removed element."));
+    }
+    sanitized.push_str(rest);
+    Ok(sanitized)
+}
+
+struct StartTag<'a> {
+    name: &'a str,
+    attributes: Vec<Attribute<'a>>,
+    self_closing: bool,
+    /// Length of the tag including the angle brackets.
+    length: usize,
+}
+
+struct Attribute<'a> {
+    name: &'a str,
+    value: &'a str,
+    /// The attribute as it appears in the tag, e.g. `fill="red"`.
+    raw: &'a str,
+}
+
+impl Attribute<'_> {
+    fn is_safe(&self) -> bool {
+        let local_name = get_local_name(self.name);
+        if local_name.len() > 2 && local_name[..2].eq_ignore_ascii_case("on") {
+            return false;
+        }
+        let normalized_value = normalize_value(self.value);
+        // animations take a list of values, each of which is applied in turn
+        let is_value_list = local_name.eq_ignore_ascii_case("values");
+        let mut values = normalized_value
+            .split(|c| is_value_list && c == ';')
+            .map(str::trim);
+        !values.any(|value| {
+            SCRIPT_SCHEMES
+                .iter()
+                .any(|scheme| value.starts_with(scheme))
+        })
+    }
+}
+
+/// Name without namespace prefix, e.g. `href` for `xlink:href`.
+fn get_local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn is_removed_element(tag: &StartTag) -> bool {
+    let local_name = get_local_name(tag.name);
+    if REMOVED_ELEMENTS
+        .iter()
+        .any(|removed| removed.eq_ignore_ascii_case(local_name))
+    {
+        return true;
+    }
+    ANIMATION_ELEMENTS
+        .iter()
+        .any(|animation| animation.eq_ignore_ascii_case(local_name))
+        && tag.attributes.iter().any(|attribute| {
+            get_local_name(attribute.name).eq_ignore_ascii_case("attributeName")
+                && get_local_name(attribute.value.trim()).eq_ignore_ascii_case("href")
+        })
+}
+
+fn push_tag(sanitized: &mut String, tag: &StartTag) {
+    sanitized.push('<');
+    sanitized.push_str(tag.name);
+    for attribute in tag
+        .attributes
+        .iter()
+        .filter(|attribute| attribute.is_safe())
+    {
+        sanitized.push(' ');
+        sanitized.push_str(attribute.raw);
+    }
+    sanitized.push_str(if tag.self_closing { "/>" } else { ">" });
+}
+
+fn parse_start_tag(input: &str) -> Result<StartTag<'_>, String> {
+    let bytes = input.as_bytes();
+    let name_end = input[1..]
+        .find(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+        .map(|index| index + 1)
+        .ok_or_else(|| String::from("SVG image ends within a tag."))?;
+    let name = &input[1..name_end];
+    if name.is_empty() {
+        return Err(String::from("SVG image contains a tag without name."));
+    }
+
+    let mut attributes = Vec::new();
+    let mut position = name_end;
+    loop {
+        while bytes.get(position).is_some_and(u8::is_ascii_whitespace) {
+            position += 1;
+        }
+        match bytes.get(position) {
+            None => return Err(String::from("SVG image ends within a tag.")),
+            Some(b'>') => {
+                return Ok(StartTag {
+                    name,
+                    attributes,
+                    self_closing: false,
+                    length: position + 1,
+                });
+            }
+            Some(b'/') if bytes.get(position + 1) == Some(&b'>') => {
+                return Ok(StartTag {
+                    name,
+                    attributes,
+                    self_closing: true,
+                    length: position + 2,
+                });
+            }
+            Some(_) => {
+                let attribute_start = position;
+                let attribute_name_end = input[position..]
+                    .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '>' || c == '/')
+                    .map(|index| index + position)
+                    .ok_or_else(|| String::from("SVG image ends within a tag."))?;
+                if attribute_name_end == position {
+                    return Err(format!("SVG image contains an invalid tag '{name}'."));
+                }
+                let attribute_name = &input[position..attribute_name_end];
+                position = attribute_name_end;
+                while bytes.get(position).is_some_and(u8::is_ascii_whitespace) {
+                    position += 1;
+                }
+                let mut value = "";
+                if bytes.get(position) == Some(&b'=') {
+                    position += 1;
+                    while bytes.get(position).is_some_and(u8::is_ascii_whitespace) {
+                        position += 1;
+                    }
+                    let (value_start, value_end, next_position) = match bytes.get(position) {
+                        Some(quote @ (b'"' | b'\'')) => {
+                            let value_end = input[position + 1..]
+                                .find(*quote as char)
+                                .map(|index| index + position + 1)
+                                .ok_or_else(|| {
+                                    String::from("SVG image ends within an attribute value.")
+                                })?;
+                            (position + 1, value_end, value_end + 1)
+                        }
+                        _ => {
+                            let value_end = input[position..]
+                                .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                                .map(|index| index + position)
+                                .ok_or_else(|| String::from("SVG image ends within a tag."))?;
+                            (position, value_end, value_end)
+                        }
+                    };
+                    value = &input[value_start..value_end];
+                    position = next_position;
+                }
+                attributes.push(Attribute {
+                    name: attribute_name,
+                    value,
+                    raw: &input[attribute_start..position],
+                });
+            }
+        }
+    }
+}
+
+/// Returns the position after the terminator.
+fn find_end(input: &str, terminator: &str, description: &str) -> Result<usize, String> {
+    input
+        .find(terminator)
+        .map(|index| index + terminator.len())
+        .ok_or_else(|| format!("SVG image ends within a {description}."))
+}
+
+/// Doctypes can contain an internal subset in square brackets with further declarations.
+fn find_doctype_end(input: &str) -> Result<usize, String> {
+    let mut in_subset = false;
+    for (index, c) in input.char_indices() {
+        match c {
+            '[' => in_subset = true,
+            ']' => in_subset = false,
+            '>' if !in_subset => return Ok(index + 1),
+            _ => {}
+        }
+    }
+    Err(String::from("SVG image ends within a doctype."))
+}
+
+/// Returns the position after the end tag of a script.
+fn find_script_end(content: &str) -> Result<usize, String> {
+    let lowercase_content = content.to_ascii_lowercase();
+    let mut search_start = 0;
+    while let Some(index) = lowercase_content[search_start..].find("</") {
+        let tag_start = search_start + index;
+        let name_start = tag_start + 2;
+        let is_script_end = lowercase_content[name_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == ':' || c == '-'))
+            .is_some_and(|name_length| {
+                get_local_name(&lowercase_content[name_start..name_start + name_length]) == "script"
+            });
+        if is_script_end {
+            return find_end(&content[tag_start..], ">", "end tag").map(|end| tag_start + end);
+        }
+        search_start = name_start;
+    }
+    Err(String::from("SVG image ends within a script."))
+}
+
+/// Decodes numeric character references and drops whitespace and control characters, which
+/// browsers ignore within URL schemes.
+fn normalize_value(value: &str) -> String {
+    let mut normalized = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(c) = rest.chars().next() {
+        if c == '&'
+            && let Some((decoded, length)) = decode_character_reference(rest)
+        {
+            if !decoded.is_whitespace() && !decoded.is_control() {
+                normalized.push(decoded.to_ascii_lowercase());
+            }
+            rest = &rest[length..];
+            continue;
+        }
+        if !c.is_whitespace() && !c.is_control() {
+            normalized.push(c.to_ascii_lowercase());
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    normalized
+}
+
+fn decode_character_reference(input: &str) -> Option<(char, usize)> {
+    let reference = input.strip_prefix("&#")?;
+    let (digits, radix, prefix_length) = match reference.strip_prefix(['x', 'X']) {
+        Some(hex) => (hex, 16, 3),
+        None => (reference, 10, 2),
+    };
+    let digits_length = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    if digits_length == 0 {
+        return None;
+    }
+    let code = u32::from_str_radix(&digits[..digits_length], radix).ok()?;
+    let terminator_length = usize::from(digits[digits_length..].starts_with(';'));
+    Some((
+        char::from_u32(code)?,
+        prefix_length + digits_length + terminator_length,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_scripts() {
+        let svg = r#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg"><script>if (a <b) { alert(1) }</script><circle r="5"/><svg:script type="text/javascript"><![CDATA[alert(2)]]></svg:script></svg>"#;
+        assert_eq!(
+            r#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg"><circle r="5"/></svg>"#,
+            sanitize_svg(svg).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_remove_event_handlers_and_script_links() {
+        let svg = r#"<svg onload="alert(1)" width='10'><a xlink:href=" java&#x09;script&#58;alert(1)" href="https://zagreus.example"><rect ONCLICK=alert(2) fill="red" /></a><set attributeName="href" to="javascript:alert(3)"/></svg>"#;
+        assert_eq!(
+            r#"<svg width='10'><a href="https://zagreus.example"><rect fill="red"/></a></svg>"#,
+            sanitize_svg(svg).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_remove_animated_links() {
+        let svg = r#"<svg><a><animate attributeName="href" values="x;javascript:alert(1)"/><animate attributeName="xlink:href" from="x" to="y"></animate><rect><animate attributeName="fill" values="red; JavaScript:alert(2) ;blue" dur="1s"/><animate attributeName="fill" values="red;blue"/></rect></a></svg>"#;
+        assert_eq!(
+            r#"<svg><a><rect><animate attributeName="fill" dur="1s"/><animate attributeName="fill" values="red;blue"/></rect></a></svg>"#,
+            sanitize_svg(svg).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_remove_embedded_documents_and_doctype() {
+        let svg = r#"<!DOCTYPE svg [<!ENTITY x "y">]><svg><!-- logo --><foreignObject><div><iframe src="x"></iframe></div></foreignObject><text>a &amp; b</text></svg>"#;
+        assert_eq!(
+            "<svg><!-- logo --><text>a &amp; b</text></svg>",
+            sanitize_svg(svg).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_remove_scripts_within_removed_elements() {
+        let svg = "<svg><foreignObject><script>a<b</script></foreignObject><g/></svg>";
+        assert_eq!("<svg><g/></svg>", sanitize_svg(svg).unwrap());
+    }
+
+    #[test]
+    fn test_reject_malformed_svg() {
+        assert!(sanitize_svg("<svg><rect fill=\"red").is_err());
+        assert!(sanitize_svg("<svg><script>alert(1)").is_err());
+        assert!(sanitize_svg("<svg><foreignObject><div></div>").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt::{Display, Formatter};
+
+use crate::config::AssetUploadConfig;
+use crate::data::asset::sniff::{is_sniffable, matches_media_type, sniff_media_type};
+use crate::data::asset::svg::sanitize_svg;
+
+const SVG_MEDIA_TYPE: &str = "image/svg+xml";
+
+/// Reasons for rejecting an uploaded asset.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssetUploadError {
+    TooLarge {
+        size: usize,
+        max_size: usize,
+    },
+    /// The media type derived from the file extension is not allowed.
+    UnsupportedMediaType(String),
+    /// The content is not of the media type derived from the file extension.
+    ContentMismatch {
+        media_type: String,
+        detected: Option<&'static str>,
+    },
+    InvalidSvg(String),
+}
+
+impl Display for AssetUploadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetUploadError::TooLarge { size, max_size } => write!(
+                f,
+                "Asset has {size} bytes, at most {max_size} bytes are allowed."
+            ),
+            AssetUploadError::UnsupportedMediaType(media_type) => {
+                write!(f, "Media type {media_type} is not allowed.")
+            }
+            AssetUploadError::ContentMismatch {
+                media_type,
+                detected: Some(detected),
+            } => write!(
+                f,
+                "Asset content is {detected} but the file extension denotes {media_type}."
+            ),
+            AssetUploadError::ContentMismatch {
+                media_type,
+                detected: None,
+            } => write!(
+                f,
+                "Asset content is not {media_type} as denoted by the file extension."
+            ),
+            AssetUploadError::InvalidSvg(reason) => write!(f, "Invalid SVG image: {reason}"),
+        }
+    }
+}
+
+/// An uploaded asset that passed the checks.
+#[derive(Debug)]
+pub struct CheckedUpload {
+    pub media_type: String,
+    /// Content to store instead of the uploaded content, e.g. an SVG image without scripts.
+    pub sanitized_content: Option<Vec<u8>>,
+}
+
+/// Checks the size of the content, that its media type is allowed and that the content is what
+/// the file extension claims it to be. SVG images are sanitized.
+pub fn check_upload(
+    config: &AssetUploadConfig,
+    extension: &str,
+    content: &[u8],
+) -> Result<CheckedUpload, AssetUploadError> {
+    if content.len() > config.max_size_bytes {
+        return Err(AssetUploadError::TooLarge {
+            size: content.len(),
+            max_size: config.max_size_bytes,
+        });
+    }
+
+    let media_type = mime_guess::from_ext(extension)
+        .first_or_octet_stream()
+        .essence_str()
+        .to_string();
+    if !config
+        .allowed_media_types
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(&media_type))
+    {
+        return Err(AssetUploadError::UnsupportedMediaType(media_type));
+    }
+
+    let detected = sniff_media_type(content);
+    let matches = match detected {
+        Some(detected) => matches_media_type(detected, &media_type),
+        // formats without magic bytes cannot be checked
+        None => !is_sniffable(&media_type),
+    };
+    if !matches {
+        return Err(AssetUploadError::ContentMismatch {
+            media_type,
+            detected,
+        });
+    }
+
+    let sanitized_content = if media_type == SVG_MEDIA_TYPE {
+        let svg = std::str::from_utf8(content).map_err(|_| {
+            AssetUploadError::InvalidSvg(String::from("SVG image is not valid UTF-8."))
+        })?;
+        let sanitized_svg = sanitize_svg(svg).map_err(AssetUploadError::InvalidSvg)?;
+        (sanitized_svg != svg).then(|| sanitized_svg.into_bytes())
+    } else {
+        None
+    };
+
+    Ok(CheckedUpload {
+        media_type,
+        sanitized_content,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    fn config() -> AssetUploadConfig {
+        AssetUploadConfig {
+            max_size_bytes: 100,
+            allowed_media_types: vec![
+                String::from("image/png"),
+                String::from("image/svg+xml"),
+                String::from("text/css"),
+                String::from("audio/m4a"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_accept_matching_content() {
+        let checked = check_upload(&config(), "PNG", PNG).unwrap();
+        assert_eq!("image/png", checked.media_type);
+        assert!(checked.sanitized_content.is_none());
+        assert!(check_upload(&config(), "css", b"body { color: red; }").is_ok());
+        assert!(check_upload(&config(), "m4a", b"\0\0\0\x20ftypisom\0\0\x02\0").is_ok());
+    }
+
+    #[test]
+    fn test_reject_invalid_uploads() {
+        assert_eq!(
+            Err(AssetUploadError::TooLarge {
+                size: 101,
+                max_size: 100
+            }),
+            check_upload(&config(), "png", &[0; 101]).map(|_| ())
+        );
+        assert_eq!(
+            Err(AssetUploadError::UnsupportedMediaType(String::from(
+                "text/html"
+            ))),
+            check_upload(&config(), "html", b"<html></html>").map(|_| ())
+        );
+        assert_eq!(
+            Err(AssetUploadError::ContentMismatch {
+                media_type: String::from("image/png"),
+                detected: Some("image/svg+xml"),
+            }),
+            check_upload(&config(), "png", b"<svg></svg>").map(|_| ())
+        );
+        assert_eq!(
+            Err(AssetUploadError::ContentMismatch {
+                media_type: String::from("image/png"),
+                detected: None,
+            }),
+            check_upload(&config(), "png", b"not an image").map(|_| ())
+        );
+        assert_eq!(
+            Err(AssetUploadError::ContentMismatch {
+                media_type: String::from("text/css"),
+                detected: Some("image/png"),
+            }),
+            check_upload(&config(), "css", PNG).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_sanitize_svg() {
+        let checked =
+            check_upload(&config(), "svg", b"<svg onload=\"alert(1)\"><g/></svg>").unwrap();
+        assert_eq!(Some(b"<svg><g/></svg>".to_vec()), checked.sanitized_content);
+        assert!(matches!(
+            check_upload(&config(), "svg", b"<svg><script>"),
+            Err(AssetUploadError::InvalidSvg(_))
+        ));
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::data::asset::AssetSource;
+use crate::websocket::message::ServerMessage;
+use crate::websocket::target::{ClientIdentity, ClientSelector};
+
+/// Last known data of a template instance. Clients connecting to an instance get this data
+/// replayed so that they show the same content as the clients which were already connected.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InstanceData {
+    texts: HashMap<String, String>,
+    classes: HashMap<String, HashMap<String, ClassState>>,
+    images: HashMap<String, ImageSource>,
+    custom_variables: HashMap<String, String>,
+    states: HashMap<String, String>,
+    /// States set for selected clients, they override the states of the whole instance in order.
+    targeted_states: Vec<TargetedState>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TargetedState {
+    selector: ClientSelector,
+    name: String,
+    value: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+enum ClassState {
+    Added,
+    Removed,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ImageSource {
+    asset: String,
+    asset_source: AssetSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alias: Option<String>,
+}
+
+impl InstanceData {
+    /// Returns whether the given message changes data that is kept for an instance.
+    pub fn is_recorded_message(message: &ServerMessage) -> bool {
+        match message {
+            ServerMessage::SetText { .. }
+            | ServerMessage::AddClass { .. }
+            | ServerMessage::RemoveClass { .. }
+            | ServerMessage::SetImageSource { .. }
+            | ServerMessage::SetCustomVariable { .. }
+            | ServerMessage::SetState { .. } => true,
+            ServerMessage::Batch { messages } => messages.iter().any(Self::is_recorded_message),
+            _ => false,
+        }
+    }
+
+    pub fn apply_message(&mut self, message: &ServerMessage) {
+        match message {
+            ServerMessage::SetText { id, text } => {
+                self.texts.insert(id.to_string(), text.to_string());
+            }
+            ServerMessage::AddClass { id, class } => {
+                self.set_class_state(id, class, ClassState::Added);
+            }
+            ServerMessage::RemoveClass { id, class } => {
+                self.set_class_state(id, class, ClassState::Removed);
+            }
+            ServerMessage::SetImageSource {
+                id,
+                asset,
+                asset_source,
+                alias,
+            } => {
+                self.images.insert(
+                    id.to_string(),
+                    ImageSource {
+                        asset: asset.to_string(),
+                        asset_source: *asset_source,
+                        alias: alias.map(String::from),
+                    },
+                );
+            }
+            ServerMessage::SetCustomVariable { name, value } => {
+                self.custom_variables
+                    .insert(name.to_string(), value.to_string());
+            }
+            ServerMessage::SetState { name, value } => {
+                // the state of the whole instance replaces the ones of selected clients
+                self.targeted_states.retain(|state| state.name != *name);
+                match value {
+                    Some(value) => {
+                        self.states.insert(name.to_string(), value.to_string());
+                    }
+                    None => {
+                        self.states.remove(*name);
+                    }
+                }
+            }
+            ServerMessage::Batch { messages } => {
+                for message in messages {
+                    self.apply_message(message);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sets the asset of the images that were set to the alias. Returns whether any image
+    /// changed.
+    pub fn repoint_image_alias(&mut self, alias: &str, asset: &str) -> bool {
+        let mut changed = false;
+        for image in self
+            .images
+            .values_mut()
+            .filter(|image| image.alias.as_deref() == Some(alias) && image.asset != asset)
+        {
+            image.asset = asset.to_string();
+            changed = true;
+        }
+        changed
+    }
+
+    /// Returns whether the given message changes data that is kept for selected clients. Only
+    /// states are kept per client, other data is kept for the whole instance only.
+    pub fn is_recorded_targeted_message(message: &ServerMessage) -> bool {
+        match message {
+            ServerMessage::SetState { .. } => true,
+            ServerMessage::Batch { messages } => {
+                messages.iter().any(Self::is_recorded_targeted_message)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn apply_targeted_message(&mut self, selector: &ClientSelector, message: &ServerMessage) {
+        match message {
+            ServerMessage::SetState { name, value } => {
+                // a newer state for the same clients replaces the older one
+                self.targeted_states
+                    .retain(|state| state.name != *name || state.selector != *selector);
+                self.targeted_states.push(TargetedState {
+                    selector: selector.clone(),
+                    name: name.to_string(),
+                    value: value.map(String::from),
+                });
+            }
+            ServerMessage::Batch { messages } => {
+                for message in messages {
+                    self.apply_targeted_message(selector, message);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The value the server wants the client to have for the given state. Returns nothing if
+    /// the state was never set or was unset for the whole instance.
+    pub fn get_desired_state(&self, name: &str, client: &ClientIdentity) -> Option<Option<&str>> {
+        let mut desired = self.states.get(name).map(|value| Some(value.as_str()));
+        for state in &self.targeted_states {
+            if state.name == name && state.selector.matches_identity(client) {
+                desired = Some(state.value.as_deref());
+            }
+        }
+        desired
+    }
+
+    /// Messages that apply the states set for selected clients to the given client, to be sent
+    /// after the messages for the whole instance.
+    pub fn get_targeted_replay_messages(&self, client: &ClientIdentity) -> Vec<ServerMessage<'_>> {
+        let names: BTreeSet<&str> = self
+            .targeted_states
+            .iter()
+            .filter(|state| state.selector.matches_identity(client))
+            .map(|state| state.name.as_str())
+            .collect();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                self.get_desired_state(name, client)
+                    .map(|value| ServerMessage::SetState { name, value })
+            })
+            .collect()
+    }
+
+    fn set_class_state(&mut self, id: &str, class: &str, state: ClassState) {
+        self.classes
+            .entry(id.to_string())
+            .or_default()
+            .insert(class.to_string(), state);
+    }
+
+    /// Messages that bring a freshly connected client to the current state of the instance.
+    pub fn get_replay_messages(&self) -> Vec<ServerMessage<'_>> {
+        let mut messages = Vec::new();
+        for (id, text) in &self.texts {
+            messages.push(ServerMessage::SetText { id, text });
+        }
+        for (id, classes) in &self.classes {
+            for (class, state) in classes {
+                messages.push(match state {
+                    ClassState::Added => ServerMessage::AddClass { id, class },
+                    ClassState::Removed => ServerMessage::RemoveClass { id, class },
+                });
+            }
+        }
+        for (id, image) in &self.images {
+            messages.push(ServerMessage::SetImageSource {
+                id,
+                asset: &image.asset,
+                asset_source: image.asset_source,
+                alias: image.alias.as_deref(),
+            });
+        }
+        for (name, value) in &self.custom_variables {
+            messages.push(ServerMessage::SetCustomVariable { name, value });
+        }
+        for (name, value) in &self.states {
+            messages.push(ServerMessage::SetState {
+                name,
+                value: Some(value),
+            });
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay_as_json(data: &InstanceData) -> Vec<String> {
+        let mut messages: Vec<String> = data
+            .get_replay_messages()
+            .iter()
+            .map(|message| serde_json::to_string(message).unwrap())
+            .collect();
+        messages.sort();
+        messages
+    }
+
+    #[test]
+    fn test_replay_keeps_last_text() {
+        let mut data = InstanceData::default();
+        data.apply_message(&ServerMessage::SetText {
+            id: "Score",
+            text: "1:0",
+        });
+        data.apply_message(&ServerMessage::SetText {
+            id: "Score",
+            text: "2:0",
+        });
+
+        assert_eq!(
+            vec![r#"{"tag":"SetText","payload":{"id":"Score","text":"2:0"}}"#],
+            replay_as_json(&data)
+        );
+    }
+
+    #[test]
+    fn test_replay_last_class_manipulation() {
+        let mut data = InstanceData::default();
+        data.apply_message(&ServerMessage::AddClass {
+            id: "Logo",
+            class: "hidden",
+        });
+        data.apply_message(&ServerMessage::RemoveClass {
+            id: "Logo",
+            class: "hidden",
+        });
+        data.apply_message(&ServerMessage::AddClass {
+            id: "Logo",
+            class: "big",
+        });
+
+        assert_eq!(
+            vec![
+                r#"{"tag":"AddClass","payload":{"id":"Logo","class":"big"}}"#,
+                r#"{"tag":"RemoveClass","payload":{"id":"Logo","class":"hidden"}}"#,
+            ],
+            replay_as_json(&data)
+        );
+    }
+
+    #[test]
+    fn test_unset_state_is_not_replayed() {
+        let mut data = InstanceData::default();
+        data.apply_message(&ServerMessage::SetState {
+            name: "phase",
+            value: Some("live"),
+        });
+        data.apply_message(&ServerMessage::SetState {
+            name: "phase",
+            value: None,
+        });
+
+        assert!(data.get_replay_messages().is_empty());
+    }
+
+    #[test]
+    fn test_serialization_roundtrip() {
+        let mut data = InstanceData::default();
+        data.apply_message(&ServerMessage::SetImageSource {
+            id: "Logo",
+            asset: "logo.png",
+            asset_source: AssetSource::Zagreus,
+            alias: Some("logo"),
+        });
+        data.apply_message(&ServerMessage::SetCustomVariable {
+            name: "--color",
+            value: "red",
+        });
+
+        let serialized = serde_json::to_string(&data).unwrap();
+        let deserialized: InstanceData = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(replay_as_json(&data), replay_as_json(&deserialized));
+    }
+
+    #[test]
+    fn test_repoint_image_alias() {
+        let mut data = InstanceData::default();
+        data.apply_message(&ServerMessage::SetImageSource {
+            id: "Logo",
+            asset: "logo.png",
+            asset_source: AssetSource::Zagreus,
+            alias: Some("logo"),
+        });
+
+        assert!(data.repoint_image_alias("logo", "new-logo.png"));
+        assert!(!data.repoint_image_alias("logo", "new-logo.png"));
+        assert!(!data.repoint_image_alias("other", "other.png"));
+        assert_eq!(
+            vec![
+                r#"{"tag":"SetImageSource","payload":{"id":"Logo","asset":"new-logo.png","assetSource":"zagreus","alias":"logo"}}"#
+            ],
+            replay_as_json(&data)
+        );
+    }
+
+    #[test]
+    fn test_batch_is_recorded() {
+        let mut data = InstanceData::default();
+        data.apply_message(&ServerMessage::Batch {
+            messages: vec![
+                ServerMessage::SetText {
+                    id: "Home",
+                    text: "1",
+                },
+                ServerMessage::SetText {
+                    id: "Home",
+                    text: "2",
+                },
+            ],
+        });
+
+        assert_eq!(
+            vec![r#"{"tag":"SetText","payload":{"id":"Home","text":"2"}}"#],
+            replay_as_json(&data)
+        );
+    }
+
+    #[test]
+    fn test_targeted_states() {
+        let left_wall = BTreeSet::from([String::from("left-wall")]);
+        let no_tags = BTreeSet::new();
+        let left = ClientIdentity {
+            id: 0,
+            name: Some("program-left"),
+            tags: &left_wall,
+        };
+        let other = ClientIdentity {
+            id: 1,
+            name: None,
+            tags: &no_tags,
+        };
+        let mut data = InstanceData::default();
+        data.apply_message(&ServerMessage::SetState {
+            name: "phase",
+            value: Some("pre"),
+        });
+        data.apply_targeted_message(
+            &ClientSelector::Name(String::from("program-left")),
+            &ServerMessage::SetState {
+                name: "phase",
+                value: Some("live"),
+            },
+        );
+        data.apply_targeted_message(
+            &ClientSelector::Name(String::from("program-left")),
+            &ServerMessage::SetState {
+                name: "logo",
+                value: None,
+            },
+        );
+
+        assert_eq!(data.get_desired_state("phase", &left), Some(Some("live")));
+        assert_eq!(data.get_desired_state("phase", &other), Some(Some("pre")));
+        assert_eq!(data.get_desired_state("logo", &left), Some(None));
+        assert_eq!(data.get_desired_state("logo", &other), None);
+        let replay: Vec<String> = data
+            .get_targeted_replay_messages(&left)
+            .iter()
+            .map(|message| serde_json::to_string(message).unwrap())
+            .collect();
+        assert_eq!(
+            replay,
+            vec![
+                r#"{"tag":"SetState","payload":{"name":"logo","value":null}}"#,
+                r#"{"tag":"SetState","payload":{"name":"phase","value":"live"}}"#,
+            ]
+        );
+        assert!(data.get_targeted_replay_messages(&other).is_empty());
+
+        data.apply_message(&ServerMessage::SetState {
+            name: "phase",
+            value: Some("post"),
+        });
+        assert_eq!(data.get_desired_state("phase", &left), Some(Some("post")));
+    }
+
+    #[test]
+    fn test_animations_are_not_recorded() {
+        let message = ServerMessage::ExecuteAnimation {
+            animation_sequence: "In",
+            queue_id: None,
+        };
+        assert!(!InstanceData::is_recorded_message(&message));
+
+        let mut data = InstanceData::default();
+        data.apply_message(&message);
+        assert!(data.get_replay_messages().is_empty());
+    }
+}
@@ -1,3 +1,7 @@
 pub mod animation;
 pub mod asset;
 pub mod config;
+pub mod instance;
+pub mod operation;
+pub mod persistence;
+pub mod preset;
@@ -0,0 +1,237 @@
+use utoipa::ToSchema;
+
+use crate::data::asset::{AssetSource, AssetStore, ResolvedAliases};
+use crate::websocket::message::ServerMessage;
+
+/// A single data manipulation on a template instance, as accepted by the batch endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum DataOperation {
+    Text {
+        id: String,
+        text: String,
+    },
+    AddClass {
+        id: String,
+        class: String,
+    },
+    RemoveClass {
+        id: String,
+        class: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Image {
+        id: String,
+        asset: String,
+        asset_source: AssetSource,
+    },
+    CustomVariable {
+        name: String,
+        value: String,
+    },
+    Animation {
+        name: String,
+        queue: Option<String>,
+    },
+    State {
+        name: String,
+        value: Option<String>,
+    },
+}
+
+impl DataOperation {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            DataOperation::Text { id, .. } => validate_not_blank("id", id),
+            DataOperation::AddClass { id, class } | DataOperation::RemoveClass { id, class } => {
+                validate_not_blank("id", id)?;
+                validate_not_blank("class", class)?;
+                if class.contains(char::is_whitespace) {
+                    return Err(format!("Class '{class}' must not contain whitespace."));
+                }
+                Ok(())
+            }
+            DataOperation::Image {
+                id,
+                asset,
+                asset_source,
+            } => {
+                validate_not_blank("id", id)?;
+                validate_not_blank("asset", asset)?;
+                if *asset_source == AssetSource::Zagreus
+                    && (asset.contains('/') || asset.contains('\\') || asset.contains(".."))
+                {
+                    return Err(format!("Asset '{asset}' contains invalid characters."));
+                }
+                Ok(())
+            }
+            DataOperation::CustomVariable { name, .. } => {
+                if !name.starts_with("--") || name.len() <= 2 {
+                    return Err(format!(
+                        "Custom variable name '{name}' must start with '--'."
+                    ));
+                }
+                Ok(())
+            }
+            DataOperation::Animation { name, queue } => {
+                validate_not_blank("name", name)?;
+                if let Some(queue) = queue {
+                    validate_not_blank("queue", queue)?;
+                }
+                Ok(())
+            }
+            DataOperation::State { name, .. } => validate_not_blank("name", name),
+        }
+    }
+
+    /// Images use the asset the alias points to if an alias is given as asset, see
+    /// [`resolve_aliases`].
+    pub fn to_message<'a>(&'a self, aliases: &'a ResolvedAliases) -> ServerMessage<'a> {
+        match self {
+            DataOperation::Text { id, text } => ServerMessage::SetText { id, text },
+            DataOperation::AddClass { id, class } => ServerMessage::AddClass { id, class },
+            DataOperation::RemoveClass { id, class } => ServerMessage::RemoveClass { id, class },
+            DataOperation::Image {
+                id,
+                asset,
+                asset_source,
+            } => {
+                let (asset, alias) = aliases.resolve(asset, *asset_source);
+                ServerMessage::SetImageSource {
+                    id,
+                    asset,
+                    asset_source: *asset_source,
+                    alias,
+                }
+            }
+            DataOperation::CustomVariable { name, value } => {
+                ServerMessage::SetCustomVariable { name, value }
+            }
+            DataOperation::Animation { name, queue } => ServerMessage::ExecuteAnimation {
+                animation_sequence: name,
+                queue_id: queue.as_deref(),
+            },
+            DataOperation::State { name, value } => ServerMessage::SetState {
+                name,
+                value: value.as_deref(),
+            },
+        }
+    }
+}
+
+/// Resolves the aliases that image operations use as asset. Every list of operations has to go
+/// through here before it is converted to messages.
+pub async fn resolve_aliases(
+    operations: &[DataOperation],
+    asset_store: &AssetStore,
+) -> ResolvedAliases {
+    asset_store
+        .resolve_aliases(operations.iter().filter_map(|operation| match operation {
+            DataOperation::Image {
+                asset,
+                asset_source: AssetSource::Zagreus,
+                ..
+            } => Some(asset.as_str()),
+            _ => None,
+        }))
+        .await
+}
+
+fn validate_not_blank(field: &str, value: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("Field '{field}' must not be empty."));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_operation() {
+        let operation: DataOperation = serde_json::from_str(
+            r#"{"type":"image","id":"Logo","asset":"logo.png","assetSource":"zagreus"}"#,
+        )
+        .unwrap();
+
+        assert!(operation.validate().is_ok());
+        assert_eq!(
+            r#"{"tag":"SetImageSource","payload":{"id":"Logo","asset":"logo.png","assetSource":"zagreus"}}"#,
+            serde_json::to_string(&operation.to_message(&ResolvedAliases::default())).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_validate_operation() {
+        let invalid_operations = [
+            DataOperation::Text {
+                id: " ".to_owned(),
+                text: "text".to_owned(),
+            },
+            DataOperation::AddClass {
+                id: "Logo".to_owned(),
+                class: "two classes".to_owned(),
+            },
+            DataOperation::Image {
+                id: "Logo".to_owned(),
+                asset: "../config.json".to_owned(),
+                asset_source: AssetSource::Zagreus,
+            },
+            DataOperation::CustomVariable {
+                name: "color".to_owned(),
+                value: "red".to_owned(),
+            },
+            DataOperation::Animation {
+                name: "In".to_owned(),
+                queue: Some(String::new()),
+            },
+        ];
+        for operation in invalid_operations {
+            assert!(
+                operation.validate().is_err(),
+                "{operation:?} should be invalid"
+            );
+        }
+
+        let valid_operation = DataOperation::CustomVariable {
+            name: "--color".to_owned(),
+            value: "red".to_owned(),
+        };
+        assert!(valid_operation.validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_aliases() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let asset_store = AssetStore::load(&path).unwrap();
+        asset_store.set_alias("logo", "home.png").await.unwrap();
+        let operations = [
+            DataOperation::Image {
+                id: "Logo".to_owned(),
+                asset: "logo".to_owned(),
+                asset_source: AssetSource::Zagreus,
+            },
+            DataOperation::Image {
+                id: "Badge".to_owned(),
+                asset: "logo".to_owned(),
+                asset_source: AssetSource::Template,
+            },
+        ];
+
+        let aliases = resolve_aliases(&operations, &asset_store).await;
+        let messages: Vec<String> = operations
+            .iter()
+            .map(|operation| serde_json::to_string(&operation.to_message(&aliases)).unwrap())
+            .collect();
+
+        assert_eq!(
+            vec![
+                r#"{"tag":"SetImageSource","payload":{"id":"Logo","asset":"home.png","assetSource":"zagreus","alias":"logo"}}"#,
+                r#"{"tag":"SetImageSource","payload":{"id":"Badge","asset":"logo","assetSource":"template"}}"#,
+            ],
+            messages
+        );
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+}
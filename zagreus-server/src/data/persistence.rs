@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::data::instance::InstanceData;
+
+const INSTANCE_FILE_EXTENSION: &str = "json";
+
+/// Stores the data of every instance as a JSON file in the instances folder.
+pub struct InstanceDataPersistence {
+    instances_folder: PathBuf,
+}
+
+impl InstanceDataPersistence {
+    pub fn new(data_folder: &Path) -> anyhow::Result<InstanceDataPersistence> {
+        let instances_folder = crate::fs::get_instances_folder(data_folder)?;
+        Ok(InstanceDataPersistence { instances_folder })
+    }
+
+    /// Loads all stored instances. Files that cannot be read are skipped so that a single broken
+    /// file does not prevent the server from starting.
+    pub fn load_all(&self) -> anyhow::Result<HashMap<String, InstanceData>> {
+        let mut instances = HashMap::new();
+        let entries = std::fs::read_dir(&self.instances_folder).with_context(|| {
+            format!(
+                "Could not read instances folder {:?}",
+                self.instances_folder
+            )
+        })?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(INSTANCE_FILE_EXTENSION) {
+                continue;
+            }
+            let Some(instance) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(decode_instance_name)
+            else {
+                warn!("Ignoring unexpected file {:?} in instances folder.", path);
+                continue;
+            };
+            match load_instance_file(&path) {
+                Ok(data) => {
+                    instances.insert(instance, data);
+                }
+                Err(err) => error!("Could not load instance data from {:?}: {:#}.", path, err),
+            }
+        }
+        Ok(instances)
+    }
+
+    pub async fn store(&self, instance: &str, data: &InstanceData) -> anyhow::Result<()> {
+        let serialized_data = serde_json::to_vec_pretty(data)?;
+        crate::fs::write_file_atomically(&self.get_instance_file_path(instance), &serialized_data)
+            .await
+    }
+
+    pub async fn delete(&self, instance: &str) -> anyhow::Result<()> {
+        let path = self.get_instance_file_path(instance);
+        if tokio::fs::try_exists(&path).await? {
+            tokio::fs::remove_file(&path)
+                .await
+                .with_context(|| format!("Could not delete instance file {path:?}"))?;
+        }
+        Ok(())
+    }
+
+    fn get_instance_file_path(&self, instance: &str) -> PathBuf {
+        self.instances_folder.join(format!(
+            "{}.{}",
+            encode_instance_name(instance),
+            INSTANCE_FILE_EXTENSION
+        ))
+    }
+}
+
+fn load_instance_file(path: &Path) -> anyhow::Result<InstanceData> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+/// Instance names come from URLs and may contain characters that are not allowed in file
+/// names, so everything except ASCII alphanumerics, `-` and `_` is percent-encoded.
+pub fn encode_instance_name(instance: &str) -> String {
+    let mut encoded = String::with_capacity(instance.len());
+    for byte in instance.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+pub fn decode_instance_name(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let high = iter.next()?;
+            let low = iter.next()?;
+            let hex = std::str::from_utf8(&[high, low]).ok()?.to_owned();
+            bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::message::ServerMessage;
+
+    #[test]
+    fn test_encode_instance_name() {
+        assert_eq!("scoreboard_1", encode_instance_name("scoreboard_1"));
+        assert_eq!("%2E%2E%2Fetc", encode_instance_name("../etc"));
+    }
+
+    #[test]
+    fn test_decode_instance_name() {
+        for name in ["scoreboard", "../etc", "lower third", "tëxt"] {
+            assert_eq!(
+                Some(name.to_owned()),
+                decode_instance_name(&encode_instance_name(name))
+            );
+        }
+        assert_eq!(None, decode_instance_name("broken%2"));
+    }
+
+    #[tokio::test]
+    async fn test_store_and_load_all() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let persistence = InstanceDataPersistence::new(&path).unwrap();
+
+        let mut data = InstanceData::default();
+        data.apply_message(&ServerMessage::SetText {
+            id: "Title",
+            text: "Halftime",
+        });
+        persistence.store("lower third", &data).await.unwrap();
+        persistence.store("other", &data).await.unwrap();
+        persistence.delete("other").await.unwrap();
+
+        let loaded = persistence.load_all().unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(
+            1,
+            loaded
+                .get("lower third")
+                .unwrap()
+                .get_replay_messages()
+                .len()
+        );
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tokio::sync::RwLock;
+
+use crate::data::operation::DataOperation;
+use crate::data::persistence::{decode_instance_name, encode_instance_name};
+
+const PRESET_FILE_EXTENSION: &str = "json";
+
+/// A named list of data operations which can be applied to an instance with a single call.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub operations: Vec<DataOperation>,
+}
+
+type InstancePresets = BTreeMap<String, Preset>;
+
+/// Keeps the presets of all instances in memory and stores the presets of every instance in a
+/// JSON file in the presets folder.
+pub struct PresetStore {
+    presets_folder: PathBuf,
+    presets: RwLock<HashMap<String, InstancePresets>>,
+}
+
+impl PresetStore {
+    pub fn load(data_folder: &Path) -> anyhow::Result<PresetStore> {
+        let presets_folder = crate::fs::get_presets_folder(data_folder)?;
+        let mut presets = HashMap::new();
+        let entries = std::fs::read_dir(&presets_folder)
+            .with_context(|| format!("Could not read presets folder {presets_folder:?}"))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(PRESET_FILE_EXTENSION) {
+                continue;
+            }
+            let Some(instance) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(decode_instance_name)
+            else {
+                warn!("Ignoring unexpected file {:?} in presets folder.", path);
+                continue;
+            };
+            match load_presets_file(&path) {
+                Ok(instance_presets) => {
+                    presets.insert(instance, instance_presets);
+                }
+                Err(err) => error!("Could not load presets from {:?}: {:#}.", path, err),
+            }
+        }
+        Ok(PresetStore {
+            presets_folder,
+            presets: RwLock::new(presets),
+        })
+    }
+
+    pub async fn get_presets(&self, instance: &str) -> Vec<(String, Preset)> {
+        self.presets
+            .read()
+            .await
+            .get(instance)
+            .map(|instance_presets| {
+                instance_presets
+                    .iter()
+                    .map(|(name, preset)| (name.clone(), preset.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub async fn get_preset(&self, instance: &str, name: &str) -> Option<Preset> {
+        self.presets
+            .read()
+            .await
+            .get(instance)
+            .and_then(|instance_presets| instance_presets.get(name))
+            .cloned()
+    }
+
+    pub async fn save_preset(
+        &self,
+        instance: &str,
+        name: &str,
+        preset: Preset,
+    ) -> anyhow::Result<()> {
+        let mut presets = self.presets.write().await;
+        let mut instance_presets = presets.get(instance).cloned().unwrap_or_default();
+        instance_presets.insert(name.to_string(), preset);
+        self.store_instance_presets(instance, &instance_presets)
+            .await?;
+        presets.insert(instance.to_string(), instance_presets);
+        Ok(())
+    }
+
+    /// Returns whether a preset with the given name existed.
+    pub async fn delete_preset(&self, instance: &str, name: &str) -> anyhow::Result<bool> {
+        let mut presets = self.presets.write().await;
+        let Some(mut instance_presets) = presets.get(instance).cloned() else {
+            return Ok(false);
+        };
+        if instance_presets.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.store_instance_presets(instance, &instance_presets)
+            .await?;
+        presets.insert(instance.to_string(), instance_presets);
+        Ok(true)
+    }
+
+    async fn store_instance_presets(
+        &self,
+        instance: &str,
+        instance_presets: &InstancePresets,
+    ) -> anyhow::Result<()> {
+        let path = self.presets_folder.join(format!(
+            "{}.{}",
+            encode_instance_name(instance),
+            PRESET_FILE_EXTENSION
+        ));
+        let serialized_presets = serde_json::to_vec_pretty(instance_presets)?;
+        crate::fs::write_file_atomically(&path, &serialized_presets).await
+    }
+}
+
+fn load_presets_file(path: &Path) -> anyhow::Result<InstancePresets> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn halftime_preset() -> Preset {
+        Preset {
+            operations: vec![DataOperation::Text {
+                id: "Title".to_owned(),
+                text: "Halftime".to_owned(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_and_reload_presets() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let store = PresetStore::load(&path).unwrap();
+        store
+            .save_preset("scoreboard", "halftime", halftime_preset())
+            .await
+            .unwrap();
+        store
+            .save_preset("scoreboard", "fulltime", halftime_preset())
+            .await
+            .unwrap();
+
+        let reloaded_store = PresetStore::load(&path).unwrap();
+        let names: Vec<String> = reloaded_store
+            .get_presets("scoreboard")
+            .await
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(vec!["fulltime", "halftime"], names);
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_preset() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let store = PresetStore::load(&path).unwrap();
+        store
+            .save_preset("scoreboard", "halftime", halftime_preset())
+            .await
+            .unwrap();
+
+        assert!(store.delete_preset("scoreboard", "halftime").await.unwrap());
+        assert!(!store.delete_preset("scoreboard", "halftime").await.unwrap());
+        assert!(store.get_preset("scoreboard", "halftime").await.is_none());
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keep_presets_if_storing_fails() {
+        let path = crate::fs::temp::prepare_temp_folder().unwrap();
+        let store = PresetStore::load(&path).unwrap();
+        store
+            .save_preset("scoreboard", "halftime", halftime_preset())
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(path.join("presets")).unwrap();
+
+        assert!(
+            store
+                .save_preset("scoreboard", "fulltime", halftime_preset())
+                .await
+                .is_err()
+        );
+        assert!(store.delete_preset("scoreboard", "halftime").await.is_err());
+        assert!(store.get_preset("scoreboard", "fulltime").await.is_none());
+        assert!(store.get_preset("scoreboard", "halftime").await.is_some());
+
+        crate::fs::temp::delete_temp_folder(&path).unwrap();
+    }
+}
@@ -1,19 +1,34 @@
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use anyhow::anyhow;
 use axum::Json;
-use axum::body::Bytes;
-use axum::extract::Extension;
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::body::{Body, Bytes};
+use axum::extract::multipart::MultipartError;
+use axum::extract::{Extension, Multipart, Query};
+use axum::http::{Request, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
 use serde_json::json;
 use sha2::Digest;
 use sha2::Sha256;
 use utoipa::ToSchema;
 
+use crate::config::{AssetUploadConfig, RenditionFormat};
+use crate::data::asset::rendition::{RENDITIONS_FOLDER_NAME, RenditionCache, is_renderable};
+use crate::data::asset::upload::{AssetUploadError, check_upload};
+use crate::data::asset::{AssetAlias, AssetMetadata, AssetStore, validate_alias};
+use crate::websocket::server::WebsocketServer;
+
 #[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub(crate) struct UploadAssetResponseDto {
     name: String,
+    /// Instances whose images were updated because the alias was pointed to the new asset.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    updated_instances: Vec<String>,
+    /// Variants of the image that were created, as configured in `assetRenditions`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    variants: Vec<String>,
 }
 
 /// Schema helper for multipart form data accepted by `upload_asset`.
@@ -23,82 +38,260 @@ pub(crate) struct UploadAssetRequestDto {
     name: String,
     #[schema(format = Binary, content_media_type = "application/octet-stream", value_type = String)]
     file: String,
+    /// Alias to point to the uploaded asset.
+    alias: Option<String>,
+    /// Whether to show the uploaded asset on elements that show the alias (`true` or `false`).
+    push: Option<bool>,
+}
+
+struct UploadedAsset {
+    name: String,
+    data: Bytes,
+    alias: Option<String>,
+    push: bool,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssetDto {
+    /// Name of the asset file as used in `/assets/{name}`.
+    name: String,
+    size: u64,
+    content_type: String,
+    uploaded_at: DateTime<Utc>,
+    /// File name the asset was uploaded with, if it was uploaded via the API.
+    original_name: Option<String>,
+}
+
+impl AssetDto {
+    fn new(name: String, metadata: AssetMetadata) -> AssetDto {
+        AssetDto {
+            name,
+            size: metadata.size,
+            content_type: metadata.content_type,
+            uploaded_at: metadata.uploaded_at,
+            original_name: metadata.original_name,
+        }
+    }
 }
 
 const ASSET_NAME_FIELD: &str = "name";
 const ASSET_DATA_FIELD: &str = "file";
+const ASSET_ALIAS_FIELD: &str = "alias";
+const ASSET_PUSH_FIELD: &str = "push";
+
+#[derive(Deserialize, Serialize, ToSchema, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum UploadErrorKind {
+    InvalidRequest,
+    InvalidName,
+    InvalidAlias,
+    TooLarge,
+    UnsupportedMediaType,
+    ContentMismatch,
+    InvalidSvg,
+    StorageFailed,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UploadErrorDto {
+    error: UploadErrorKind,
+    message: String,
+}
+
+impl UploadErrorDto {
+    fn new(error: UploadErrorKind, message: impl Into<String>) -> UploadErrorDto {
+        UploadErrorDto {
+            error,
+            message: message.into(),
+        }
+    }
+
+    fn get_status(&self) -> StatusCode {
+        match self.error {
+            UploadErrorKind::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            UploadErrorKind::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            UploadErrorKind::StorageFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl From<AssetUploadError> for UploadErrorDto {
+    fn from(err: AssetUploadError) -> Self {
+        let kind = match err {
+            AssetUploadError::TooLarge { .. } => UploadErrorKind::TooLarge,
+            AssetUploadError::UnsupportedMediaType(_) => UploadErrorKind::UnsupportedMediaType,
+            AssetUploadError::ContentMismatch { .. } => UploadErrorKind::ContentMismatch,
+            AssetUploadError::InvalidSvg(_) => UploadErrorKind::InvalidSvg,
+        };
+        UploadErrorDto::new(kind, err.to_string())
+    }
+}
+
+impl From<MultipartError> for UploadErrorDto {
+    fn from(err: MultipartError) -> Self {
+        let kind = if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            UploadErrorKind::TooLarge
+        } else {
+            UploadErrorKind::InvalidRequest
+        };
+        UploadErrorDto::new(kind, err.body_text())
+    }
+}
+
+impl IntoResponse for UploadErrorDto {
+    fn into_response(self) -> Response {
+        (self.get_status(), Json(self)).into_response()
+    }
+}
 
 #[utoipa::path(
     post,
     path = "/api/asset",
     tag = "Asset",
     summary = "Upload an asset",
+    description = "The media type of the asset is derived from the file extension of its name, it has to be allowed in the configuration and match the content of the file. Scripts and event handlers are removed from SVG images. The configured variants of PNG, JPEG and WebP images are created right away.",
     request_body(
         content = UploadAssetRequestDto,
-        description = "Multipart form data containing `name` and `file` fields",
+        description = "Multipart form data containing `name` and `file` fields and optionally an `alias` to point to the asset",
         content_type = "multipart/form-data"
     ),
     responses(
         (status = 200, description = "Asset uploaded", body = UploadAssetResponseDto),
-        (status = 400, description = "Asset name invalid", body = String),
-        (status = 500, description = "Upload failed", body = String),
+        (status = 400, description = "Invalid request, name or alias or content that does not match the file extension", body = UploadErrorDto),
+        (status = 413, description = "Asset too large", body = UploadErrorDto),
+        (status = 415, description = "Media type not allowed", body = UploadErrorDto),
+        (status = 500, description = "Upload failed", body = UploadErrorDto),
     )
 )]
 pub(crate) async fn upload_asset(
     Extension(assets_folder): Extension<PathBuf>,
-    multipart: axum::extract::Multipart,
-) -> impl IntoResponse {
-    match get_asset_data(multipart).await {
-        Ok((asset_name, asset_data)) => {
-            if asset_name.contains(std::path::MAIN_SEPARATOR) || asset_name.contains("..") {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(json!("Asset name contains invalid character")),
-                );
-            }
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Extension(upload_config): Extension<Arc<AssetUploadConfig>>,
+    Extension(rendition_cache): Extension<Arc<RenditionCache>>,
+    multipart: Multipart,
+) -> Result<Json<UploadAssetResponseDto>, UploadErrorDto> {
+    let asset = get_asset_data(multipart).await?;
+    if asset.name.contains(std::path::MAIN_SEPARATOR) || asset.name.contains("..") {
+        return Err(UploadErrorDto::new(
+            UploadErrorKind::InvalidName,
+            "Asset name contains invalid character.",
+        ));
+    }
+    if let Some(alias) = &asset.alias {
+        validate_alias(alias)
+            .map_err(|message| UploadErrorDto::new(UploadErrorKind::InvalidAlias, message))?;
+    }
+    let extension = Path::new(&asset.name)
+        .extension()
+        .and_then(|val| val.to_str())
+        .ok_or_else(|| {
+            UploadErrorDto::new(
+                UploadErrorKind::InvalidName,
+                "Asset name has no file extension.",
+            )
+        })?;
 
-            let path = PathBuf::from(asset_name);
-
-            match path.extension().and_then(|val| val.to_str()) {
-                Some(extension) => {
-                    match write_asset_file(&assets_folder, extension, asset_data).await {
-                        Ok(asset_name) => (
-                            StatusCode::OK,
-                            Json(json!(UploadAssetResponseDto { name: asset_name })),
-                        ),
-                        Err(err) => {
-                            error!("Could not upload asset successfully: {}.", err);
-                            (
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                Json(json!("Could not upload asset.")),
-                            )
-                        }
-                    }
-                }
-                None => {
-                    error!("Could not parse asset name");
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!("Invalid asset name.")),
+    let checked_upload = check_upload(&upload_config, extension, &asset.data)?;
+    let content = match checked_upload.sanitized_content {
+        Some(sanitized_content) => {
+            info!("Sanitized uploaded SVG image {}.", asset.name);
+            Bytes::from(sanitized_content)
+        }
+        None => asset.data,
+    };
+
+    let asset_name = write_and_index_asset_file(
+        &assets_folder,
+        &asset_store,
+        &asset.name,
+        extension,
+        &checked_upload.media_type,
+        content,
+    )
+    .await
+    .map_err(|err| {
+        error!("Could not upload asset successfully: {:#}.", err);
+        UploadErrorDto::new(UploadErrorKind::StorageFailed, "Could not upload asset.")
+    })?;
+    let updated_instances = match &asset.alias {
+        Some(alias) => {
+            point_alias(&asset_store, &server, alias, &asset_name, asset.push)
+                .await
+                .map_err(|err| {
+                    error!("Could not point alias {}: {:#}.", alias, err);
+                    UploadErrorDto::new(
+                        UploadErrorKind::StorageFailed,
+                        "Could not point alias to asset.",
                     )
-                }
-            }
+                })?
+                .updated_instances
         }
+        None => Vec::new(),
+    };
+    let variants = rendition_cache.create_variants(&asset_name).await;
+    Ok(Json(UploadAssetResponseDto {
+        name: asset_name,
+        updated_instances,
+        variants,
+    }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RenditionQuery {
+    w: Option<u32>,
+    variant: Option<String>,
+    format: Option<RenditionFormat>,
+}
+
+/// Rewrites requests for a rendition of an image (e.g. `/assets/{name}?w=640`) to the cached
+/// rendition, which is created first if necessary. Other assets are served as they are.
+pub(crate) async fn map_rendition_url(
+    Extension(rendition_cache): Extension<Arc<RenditionCache>>,
+    Query(query): Query<RenditionQuery>,
+    req: Request<Body>,
+) -> Result<Request<Body>, StatusCode> {
+    let Some(asset_name) = req.uri().path().strip_prefix("/assets/") else {
+        return Ok(req);
+    };
+    if asset_name.contains('/') || asset_name.starts_with('.') || !is_renderable(asset_name) {
+        return Ok(req);
+    }
+    let spec = match (&query.variant, query.w) {
+        (Some(variant), _) => rendition_cache.get_spec_for_variant(variant, query.format),
+        (None, Some(width)) => rendition_cache.get_spec_for_width(width, query.format),
+        (None, None) => return Ok(req),
+    }
+    .ok_or(StatusCode::NOT_FOUND)?;
+    let file_name = match rendition_cache.get_rendition(asset_name, spec).await {
+        Ok(Some(file_name)) => file_name,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
         Err(err) => {
-            error!("Could not upload asset to server: {}.", err);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!("Could not parse upload request.")),
-            )
+            error!(
+                "Could not get rendition of asset {}: {:#}.",
+                asset_name, err
+            );
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
-    }
+    };
+    let (mut parts, body) = req.into_parts();
+    parts.uri = format!("/assets/{RENDITIONS_FOLDER_NAME}/{file_name}")
+        .parse::<Uri>()
+        .map_err(|err| {
+            error!("URI was invalid: {}.", err);
+            StatusCode::BAD_REQUEST
+        })?;
+    Ok(Request::from_parts(parts, body))
 }
 
-async fn get_asset_data(
-    mut multipart: axum::extract::Multipart,
-) -> anyhow::Result<(String, Bytes)> {
+async fn get_asset_data(mut multipart: Multipart) -> Result<UploadedAsset, UploadErrorDto> {
     let mut asset_name: Option<String> = None;
     let mut asset_data: Option<Bytes> = None;
+    let mut alias: Option<String> = None;
+    let mut push = false;
     while let Some(field) = multipart.next_field().await? {
         if let Some(name) = field.name() {
             if name.eq(ASSET_NAME_FIELD) {
@@ -107,15 +300,48 @@ async fn get_asset_data(
             } else if name.eq(ASSET_DATA_FIELD) {
                 let data = field.bytes().await?;
                 asset_data = Some(data);
+            } else if name.eq(ASSET_ALIAS_FIELD) {
+                alias = Some(field.text().await?.trim().to_string());
+            } else if name.eq(ASSET_PUSH_FIELD) {
+                push = field.text().await?.trim().parse().map_err(|_| {
+                    UploadErrorDto::new(
+                        UploadErrorKind::InvalidRequest,
+                        "Field push must be true or false.",
+                    )
+                })?;
             }
         }
     }
     if let Some(asset_name) = asset_name
         && let Some(asset_data) = asset_data
     {
-        return Ok((asset_name, asset_data));
+        return Ok(UploadedAsset {
+            name: asset_name,
+            data: asset_data,
+            alias,
+            push,
+        });
     }
-    Err(anyhow!("Multipart request did not have expected format."))
+    Err(UploadErrorDto::new(
+        UploadErrorKind::InvalidRequest,
+        "Multipart request did not have expected format.",
+    ))
+}
+
+async fn write_and_index_asset_file(
+    assets_folder: &Path,
+    asset_store: &AssetStore,
+    original_name: &str,
+    extension: &str,
+    media_type: &str,
+    asset_bytes: Bytes,
+) -> anyhow::Result<String> {
+    let size = asset_bytes.len() as u64;
+    let saved_asset_name = write_asset_file(assets_folder, extension, asset_bytes).await?;
+    asset_store
+        .add_asset(&saved_asset_name, original_name, size, media_type)
+        .await?;
+    Ok(saved_asset_name)
 }
 
 async fn write_asset_file(
@@ -134,3 +360,287 @@ async fn write_asset_file(
 
     Ok(saved_asset_name)
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/asset",
+    tag = "Asset",
+    summary = "List assets",
+    responses(
+        (status = 200, description = "Assets in the assets folder", body = Vec<AssetDto>),
+    )
+)]
+pub(crate) async fn get_assets(
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+) -> impl IntoResponse {
+    let assets: Vec<AssetDto> = asset_store
+        .get_assets()
+        .await
+        .into_iter()
+        .map(|(name, metadata)| AssetDto::new(name, metadata))
+        .collect();
+    Json(assets)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/asset/{name}",
+    tag = "Asset",
+    summary = "Get asset metadata",
+    params(
+        ("name" = String, Path, description = "Asset name as returned by the upload")
+    ),
+    responses(
+        (status = 200, description = "Asset metadata", body = AssetDto),
+        (status = 404, description = "Asset not found"),
+    )
+)]
+pub(crate) async fn get_asset(
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+) -> impl IntoResponse {
+    match asset_store.get_asset(&name).await {
+        Some(metadata) => Json(AssetDto::new(name, metadata)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/asset/{name}",
+    tag = "Asset",
+    summary = "Delete asset",
+    description = "Deletes the asset file. Clients that currently show the asset keep showing it until they reload it.",
+    params(
+        ("name" = String, Path, description = "Asset name as returned by the upload")
+    ),
+    responses(
+        (status = 200, description = "Asset deleted"),
+        (status = 404, description = "Asset not found"),
+        (status = 409, description = "Aliases point to the asset", body = String),
+        (status = 500, description = "Asset could not be deleted", body = String),
+    )
+)]
+pub(crate) async fn delete_asset(
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+) -> impl IntoResponse {
+    let aliases = asset_store.get_aliases_of_asset(&name).await;
+    if !aliases.is_empty() {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!(format!(
+                "Asset is used by the aliases {}.",
+                aliases.join(", ")
+            ))),
+        )
+            .into_response();
+    }
+    match asset_store.delete_asset(&name).await {
+        Ok(true) => StatusCode::OK.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!("Could not delete asset {}: {:#}.", name, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!("Could not delete asset.")),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssetAliasDto {
+    alias: String,
+    asset: String,
+    updated_at: DateTime<Utc>,
+    /// Assets the alias pointed to earlier, the most recent one last.
+    history: Vec<AssetAliasTargetDto>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssetAliasTargetDto {
+    asset: String,
+    updated_at: DateTime<Utc>,
+}
+
+impl AssetAliasDto {
+    fn new(alias: String, asset_alias: AssetAlias) -> AssetAliasDto {
+        AssetAliasDto {
+            alias,
+            asset: asset_alias.asset,
+            updated_at: asset_alias.updated_at,
+            history: asset_alias
+                .history
+                .into_iter()
+                .map(|target| AssetAliasTargetDto {
+                    asset: target.asset,
+                    updated_at: target.updated_at,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetAssetAliasDto {
+    asset: String,
+    /// Whether to show the new asset on elements that show the alias.
+    #[serde(default)]
+    push: bool,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetAssetAliasResultDto {
+    /// Whether the alias pointed to a different asset before.
+    repointed: bool,
+    /// Instances whose images were updated to the new asset.
+    updated_instances: Vec<String>,
+}
+
+async fn point_alias(
+    asset_store: &AssetStore,
+    server: &WebsocketServer,
+    alias: &str,
+    asset: &str,
+    push: bool,
+) -> anyhow::Result<SetAssetAliasResultDto> {
+    let repointed = asset_store.set_alias(alias, asset).await?;
+    let updated_instances = if repointed && push {
+        server.update_image_alias(alias, asset).await
+    } else {
+        Vec::new()
+    };
+    Ok(SetAssetAliasResultDto {
+        repointed,
+        updated_instances,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/asset/alias",
+    tag = "Asset",
+    summary = "List asset aliases",
+    responses(
+        (status = 200, description = "Asset aliases", body = Vec<AssetAliasDto>),
+    )
+)]
+pub(crate) async fn get_asset_aliases(
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+) -> impl IntoResponse {
+    let aliases: Vec<AssetAliasDto> = asset_store
+        .get_aliases()
+        .await
+        .into_iter()
+        .map(|(alias, asset_alias)| AssetAliasDto::new(alias, asset_alias))
+        .collect();
+    Json(aliases)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/asset/alias/{alias}",
+    tag = "Asset",
+    summary = "Get asset alias",
+    params(
+        ("alias" = String, Path, description = "Alias name")
+    ),
+    responses(
+        (status = 200, description = "Asset alias with its history", body = AssetAliasDto),
+        (status = 404, description = "Alias not found"),
+    )
+)]
+pub(crate) async fn get_asset_alias(
+    axum::extract::Path(alias): axum::extract::Path<String>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+) -> impl IntoResponse {
+    match asset_store.get_alias(&alias).await {
+        Some(asset_alias) => Json(AssetAliasDto::new(alias, asset_alias)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/asset/alias/{alias}",
+    tag = "Asset",
+    summary = "Point asset alias to an asset",
+    description = "Creates the alias or points it to another asset, the asset it pointed to before is kept in its history. With `push`, elements that were set to the alias show the new asset right away, otherwise they keep the previous asset until they are set again.",
+    params(
+        ("alias" = String, Path, description = "Alias name")
+    ),
+    request_body = SetAssetAliasDto,
+    responses(
+        (status = 200, description = "Alias updated", body = SetAssetAliasResultDto),
+        (status = 400, description = "Alias invalid", body = String),
+        (status = 404, description = "Asset not found", body = String),
+        (status = 500, description = "Alias could not be updated", body = String),
+    )
+)]
+pub(crate) async fn set_asset_alias(
+    axum::extract::Path(alias): axum::extract::Path<String>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Json(payload): Json<SetAssetAliasDto>,
+) -> impl IntoResponse {
+    if let Err(message) = validate_alias(&alias) {
+        return (StatusCode::BAD_REQUEST, Json(json!(message))).into_response();
+    }
+    if asset_store.get_asset(&payload.asset).await.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!(format!("Asset {} does not exist.", payload.asset))),
+        )
+            .into_response();
+    }
+    match point_alias(&asset_store, &server, &alias, &payload.asset, payload.push).await {
+        Ok(result) => Json(result).into_response(),
+        Err(err) => {
+            error!("Could not point alias {}: {:#}.", alias, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!("Could not update alias.")),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/asset/alias/{alias}",
+    tag = "Asset",
+    summary = "Delete asset alias",
+    description = "Deletes the alias only, the assets it pointed to are kept.",
+    params(
+        ("alias" = String, Path, description = "Alias name")
+    ),
+    responses(
+        (status = 200, description = "Alias deleted"),
+        (status = 404, description = "Alias not found"),
+        (status = 500, description = "Alias could not be deleted", body = String),
+    )
+)]
+pub(crate) async fn delete_asset_alias(
+    axum::extract::Path(alias): axum::extract::Path<String>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+) -> impl IntoResponse {
+    match asset_store.delete_alias(&alias).await {
+        Ok(true) => StatusCode::OK.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!("Could not delete alias {}: {:#}.", alias, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!("Could not delete alias.")),
+            )
+                .into_response()
+        }
+    }
+}
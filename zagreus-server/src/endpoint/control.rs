@@ -0,0 +1,460 @@
+//! Websocket for controllers that send many commands or want to be notified about instance
+//! events. Every request carries an id that is repeated in its response. Commands are sent to the
+//! clients in the order the requests arrive. Waiting for acknowledgements does not hold up later
+//! requests, so responses of requests with `wait` can arrive after responses of later requests.
+
+use crate::data::asset::{AssetStore, ResolvedAliases};
+use crate::data::operation::{DataOperation, resolve_aliases};
+use crate::endpoint::data::{ValidationErrorDto, validate_operations};
+use crate::endpoint::state::{GetStateDto, collect_state};
+use crate::endpoint::target::{CommandResultDto, get_ack_timeout, send_message};
+use crate::event::ServerEvent;
+use crate::websocket::message::ServerMessage;
+use crate::websocket::server::WebsocketServer;
+use crate::websocket::target::ClientSelector;
+use axum::Extension;
+use axum::extract::WebSocketUpgrade;
+use axum::extract::ws::{Message, WebSocket};
+use axum::response::IntoResponse;
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::UnboundedSender;
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ControlRequest {
+    id: u64,
+    #[serde(flatten)]
+    command: ControlCommand,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(
+    tag = "type",
+    rename_all = "kebab-case",
+    rename_all_fields = "camelCase"
+)]
+pub(crate) enum ControlCommand {
+    /// Same as the data and state endpoints.
+    Data {
+        instance: String,
+        operation: DataOperation,
+        client: Option<ClientSelector>,
+        #[serde(default)]
+        wait: bool,
+        timeout: Option<u64>,
+    },
+    /// Same as the batch endpoint.
+    Batch {
+        instance: String,
+        operations: Vec<DataOperation>,
+        client: Option<ClientSelector>,
+        #[serde(default)]
+        wait: bool,
+        timeout: Option<u64>,
+    },
+    ClearData {
+        instance: String,
+    },
+    GetState {
+        instance: String,
+        name: String,
+        client: Option<ClientSelector>,
+    },
+    /// Without an instance, the events of all instances are subscribed.
+    Subscribe {
+        instance: Option<String>,
+    },
+    Unsubscribe {
+        instance: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "kebab-case",
+    rename_all_fields = "camelCase"
+)]
+enum ControlReply {
+    Response {
+        id: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<ControlResult>,
+    },
+    Error {
+        id: Option<u64>,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        validation: Option<ValidationErrorDto>,
+    },
+    Event {
+        event: ServerEvent,
+    },
+    /// The controller did not keep up and missed some events.
+    Lagged {
+        skipped: u64,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ControlResult {
+    Command(CommandResultDto),
+    State(GetStateDto),
+}
+
+/// What requests of a controller are executed with.
+struct ControlContext<'a> {
+    server: &'a WebsocketServer,
+    asset_store: &'a AssetStore,
+}
+
+impl ControlContext<'_> {
+    async fn resolve_aliases(&self, operations: &[DataOperation]) -> ResolvedAliases {
+        resolve_aliases(operations, self.asset_store).await
+    }
+}
+
+/// Instances whose events the controller subscribed to.
+#[derive(Default)]
+struct Subscription {
+    all_instances: bool,
+    instances: HashSet<String>,
+}
+
+impl Subscription {
+    fn subscribe(&mut self, instance: Option<String>) {
+        match instance {
+            Some(instance) => {
+                self.instances.insert(instance);
+            }
+            None => self.all_instances = true,
+        }
+    }
+
+    fn unsubscribe(&mut self, instance: Option<String>) {
+        match instance {
+            Some(instance) => {
+                self.instances.remove(&instance);
+            }
+            None => {
+                self.all_instances = false;
+                self.instances.clear();
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.all_instances || !self.instances.is_empty()
+    }
+
+    fn matches(&self, instance: &str) -> bool {
+        self.all_instances || self.instances.contains(instance)
+    }
+
+    /// Subscribes to the server events with the first subscribed instance and unsubscribes
+    /// after the last one.
+    fn update_events(
+        &self,
+        events: &mut Option<broadcast::Receiver<Arc<ServerEvent>>>,
+        server: &WebsocketServer,
+    ) {
+        if !self.is_active() {
+            *events = None;
+        } else if events.is_none() {
+            *events = Some(server.subscribe_events());
+        }
+    }
+}
+
+/// Waits forever without a subscription.
+async fn receive_event(
+    events: &mut Option<broadcast::Receiver<Arc<ServerEvent>>>,
+) -> Result<Arc<ServerEvent>, RecvError> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+pub(crate) async fn control_ws_handler(
+    ws: WebSocketUpgrade,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(|websocket| handle_control_socket(websocket, server, asset_store))
+}
+
+async fn handle_control_socket(
+    websocket: WebSocket,
+    server: Arc<WebsocketServer>,
+    asset_store: Arc<AssetStore>,
+) {
+    info!("Connected to new control client.");
+    let (mut sink, mut stream) = websocket.split();
+    let (reply_sender, mut reply_receiver) = tokio::sync::mpsc::unbounded_channel();
+    // only subscribed while the controller wants events, so that commands are not published
+    // as events for nobody
+    let mut events = None;
+    let mut subscription = Subscription::default();
+    let context = ControlContext {
+        server: &server,
+        asset_store: &asset_store,
+    };
+
+    loop {
+        let reply = tokio::select! {
+            message_result = stream.next() => match message_result {
+                Some(Ok(Message::Text(text))) => {
+                    handle_request(text.as_bytes(), &context, &mut subscription, &reply_sender)
+                        .await;
+                    subscription.update_events(&mut events, &server);
+                    continue;
+                }
+                Some(Ok(Message::Binary(data))) => {
+                    handle_request(&data, &context, &mut subscription, &reply_sender).await;
+                    subscription.update_events(&mut events, &server);
+                    continue;
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => continue,
+                Some(Err(err)) => {
+                    error!("Could not receive message from control client: {}.", err);
+                    break;
+                }
+            },
+            Some(reply) = reply_receiver.recv() => reply,
+            event_result = receive_event(&mut events) => match event_result {
+                Ok(event) if subscription.matches(event.get_instance()) => ControlReply::Event {
+                    event: event.as_ref().clone(),
+                },
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => ControlReply::Lagged { skipped },
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if let Err(err) = send_reply(&mut sink, &reply).await {
+            error!("Could not send reply to control client: {}.", err);
+            break;
+        }
+    }
+    debug!("Control client has disconnected.");
+}
+
+async fn send_reply(
+    sink: &mut SplitSink<WebSocket, Message>,
+    reply: &ControlReply,
+) -> anyhow::Result<()> {
+    let serialized = serde_json::to_string(reply)?;
+    sink.send(Message::Text(serialized.into())).await?;
+    Ok(())
+}
+
+async fn handle_request(
+    data: &[u8],
+    context: &ControlContext<'_>,
+    subscription: &mut Subscription,
+    reply_sender: &UnboundedSender<ControlReply>,
+) {
+    let request = match serde_json::from_slice::<ControlRequest>(data) {
+        Ok(request) => request,
+        Err(err) => {
+            // still try to tell the controller which request was invalid
+            let id = serde_json::from_slice::<serde_json::Value>(data)
+                .ok()
+                .and_then(|value| value.get("id").and_then(serde_json::Value::as_u64));
+            let _ = reply_sender.send(error_reply(id, format!("Invalid request: {err}")));
+            return;
+        }
+    };
+
+    let id = request.id;
+    let reply = match request.command {
+        ControlCommand::Subscribe { instance } => {
+            subscription.subscribe(instance);
+            ControlReply::Response { id, result: None }
+        }
+        ControlCommand::Unsubscribe { instance } => {
+            subscription.unsubscribe(instance);
+            ControlReply::Response { id, result: None }
+        }
+        ControlCommand::Data {
+            instance,
+            operation,
+            client,
+            wait,
+            timeout,
+        } => {
+            if let Err(message) = operation.validate() {
+                error_reply(Some(id), message)
+            } else {
+                let aliases = context
+                    .resolve_aliases(std::slice::from_ref(&operation))
+                    .await;
+                send_command(
+                    id,
+                    &instance,
+                    context.server,
+                    &operation.to_message(&aliases),
+                    client.as_ref(),
+                    get_ack_timeout(wait, timeout),
+                    reply_sender,
+                )
+                .await;
+                return;
+            }
+        }
+        ControlCommand::Batch {
+            instance,
+            operations,
+            client,
+            wait,
+            timeout,
+        } => {
+            if let Err(errors) = validate_operations(&operations) {
+                ControlReply::Error {
+                    id: Some(id),
+                    message: errors.message.clone(),
+                    validation: Some(errors),
+                }
+            } else {
+                let aliases = context.resolve_aliases(&operations).await;
+                let message = ServerMessage::Batch {
+                    messages: operations
+                        .iter()
+                        .map(|operation| operation.to_message(&aliases))
+                        .collect(),
+                };
+                send_command(
+                    id,
+                    &instance,
+                    context.server,
+                    &message,
+                    client.as_ref(),
+                    get_ack_timeout(wait, timeout),
+                    reply_sender,
+                )
+                .await;
+                return;
+            }
+        }
+        ControlCommand::ClearData { instance } => {
+            context.server.clear_instance_data(&instance).await;
+            ControlReply::Response { id, result: None }
+        }
+        ControlCommand::GetState {
+            instance,
+            name,
+            client,
+        } => ControlReply::Response {
+            id,
+            result: Some(ControlResult::State(
+                collect_state(context.server, &instance, &name, client.as_ref()).await,
+            )),
+        },
+    };
+    let _ = reply_sender.send(reply);
+}
+
+/// Sends the message before the next request is read, so that the clients apply the commands of
+/// a controller in the order they were sent. Only waiting for acknowledgements happens in the
+/// background, where it does not hold up later requests.
+async fn send_command(
+    id: u64,
+    instance: &str,
+    server: &WebsocketServer,
+    message: &ServerMessage<'_>,
+    client: Option<&ClientSelector>,
+    wait_timeout: Option<Duration>,
+    reply_sender: &UnboundedSender<ControlReply>,
+) {
+    let Some(timeout) = wait_timeout else {
+        send_message(instance, server, message, client, None).await;
+        let _ = reply_sender.send(ControlReply::Response { id, result: None });
+        return;
+    };
+    let pending_command = server
+        .send_message_with_acks(instance, client, message, timeout)
+        .await;
+    let reply_sender = reply_sender.clone();
+    tokio::spawn(async move {
+        let acks = pending_command.wait_for_acks().await;
+        // the controller might have disconnected in the meantime
+        let _ = reply_sender.send(ControlReply::Response {
+            id,
+            result: Some(ControlResult::Command(CommandResultDto::from(acks))),
+        });
+    });
+}
+
+fn error_reply(id: Option<u64>, message: String) -> ControlReply {
+    ControlReply::Error {
+        id,
+        message,
+        validation: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::target::ClientGroup;
+
+    #[test]
+    fn test_deserialize_request() {
+        let request: ControlRequest = serde_json::from_str(
+            r#"{"id": 4, "type": "data", "instance": "main", "operation": {"type": "text", "id": "title", "text": "Hello"}, "client": {"tags": ["left-wall"]}, "wait": true}"#,
+        )
+        .unwrap();
+
+        assert_eq!(request.id, 4);
+        match request.command {
+            ControlCommand::Data {
+                instance,
+                operation: DataOperation::Text { id, text },
+                client,
+                wait,
+                timeout,
+            } => {
+                assert_eq!(instance, "main");
+                assert_eq!(id, "title");
+                assert_eq!(text, "Hello");
+                assert_eq!(
+                    client,
+                    Some(ClientSelector::Group(ClientGroup {
+                        tags: vec![String::from("left-wall")],
+                        ..ClientGroup::default()
+                    }))
+                );
+                assert!(wait);
+                assert_eq!(timeout, None);
+            }
+            command => panic!("unexpected command {command:?}"),
+        }
+    }
+
+    #[test]
+    fn test_subscription() {
+        let mut subscription = Subscription::default();
+        assert!(!subscription.is_active());
+
+        subscription.subscribe(Some(String::from("main")));
+        assert!(subscription.matches("main"));
+        assert!(!subscription.matches("other"));
+
+        subscription.subscribe(None);
+        assert!(subscription.matches("other"));
+
+        subscription.unsubscribe(Some(String::from("main")));
+        assert!(subscription.matches("main"));
+
+        subscription.unsubscribe(None);
+        assert!(!subscription.is_active());
+        assert!(!subscription.matches("main"));
+    }
+}
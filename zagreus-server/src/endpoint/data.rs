@@ -1,8 +1,14 @@
 use crate::WebsocketServer;
-use crate::data::asset::AssetSource;
+use crate::data::asset::{AssetSource, AssetStore, ResolvedAliases};
+use crate::data::operation::{DataOperation, resolve_aliases};
+use crate::endpoint::target::{
+    AckQueryParams, CommandResultDto, InstancesQueryParams, MultiInstanceResultDto,
+    send_message_to_instances, send_message_to_target,
+};
 use crate::websocket::message::ServerMessage;
+use crate::websocket::target::ClientSelector;
 use axum::Json;
-use axum::extract::{Extension, Path};
+use axum::extract::{Extension, Path, Query};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use std::sync::Arc;
@@ -12,14 +18,14 @@ use utoipa::ToSchema;
 pub(crate) struct SetTextDto {
     id: String,
     text: String,
-    client: Option<usize>,
+    client: Option<ClientSelector>,
 }
 
 #[derive(Deserialize, Serialize, ToSchema)]
 pub(crate) struct ManipulateClassDto {
     id: String,
     class: String,
-    client: Option<usize>,
+    client: Option<ClientSelector>,
 }
 
 #[derive(Deserialize, Serialize, ToSchema)]
@@ -28,7 +34,7 @@ pub(crate) struct SetImageSourceDto {
     id: String,
     asset: String,
     asset_source: AssetSource,
-    client: Option<usize>,
+    client: Option<ClientSelector>,
 }
 
 #[derive(Deserialize, Serialize, ToSchema)]
@@ -36,7 +42,7 @@ pub(crate) struct SetImageSourceDto {
 pub(crate) struct SetCustomVariableDto {
     name: String,
     value: String,
-    client: Option<usize>,
+    client: Option<ClientSelector>,
 }
 
 #[derive(Deserialize, Serialize, ToSchema)]
@@ -44,7 +50,100 @@ pub(crate) struct SetCustomVariableDto {
 pub(crate) struct ExecuteAnimationDto {
     name: String,
     queue: Option<String>,
-    client: Option<usize>,
+    client: Option<ClientSelector>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchDto {
+    operations: Vec<DataOperation>,
+    client: Option<ClientSelector>,
+}
+
+impl SetTextDto {
+    fn to_message(&self) -> ServerMessage<'_> {
+        ServerMessage::SetText {
+            id: &self.id,
+            text: &self.text,
+        }
+    }
+}
+
+impl ManipulateClassDto {
+    fn to_add_message(&self) -> ServerMessage<'_> {
+        ServerMessage::AddClass {
+            id: &self.id,
+            class: &self.class,
+        }
+    }
+
+    fn to_remove_message(&self) -> ServerMessage<'_> {
+        ServerMessage::RemoveClass {
+            id: &self.id,
+            class: &self.class,
+        }
+    }
+}
+
+impl SetImageSourceDto {
+    async fn resolve_aliases(&self, asset_store: &AssetStore) -> ResolvedAliases {
+        asset_store.resolve_aliases([self.asset.as_str()]).await
+    }
+
+    fn to_message<'a>(&'a self, aliases: &'a ResolvedAliases) -> ServerMessage<'a> {
+        let (asset, alias) = aliases.resolve(&self.asset, self.asset_source);
+        ServerMessage::SetImageSource {
+            id: &self.id,
+            asset,
+            asset_source: self.asset_source,
+            alias,
+        }
+    }
+}
+
+impl SetCustomVariableDto {
+    fn to_message(&self) -> ServerMessage<'_> {
+        ServerMessage::SetCustomVariable {
+            name: &self.name,
+            value: &self.value,
+        }
+    }
+}
+
+impl ExecuteAnimationDto {
+    fn to_message(&self) -> ServerMessage<'_> {
+        ServerMessage::ExecuteAnimation {
+            animation_sequence: &self.name,
+            queue_id: self.queue.as_deref(),
+        }
+    }
+}
+
+impl BatchDto {
+    fn to_message<'a>(&'a self, aliases: &'a ResolvedAliases) -> ServerMessage<'a> {
+        ServerMessage::Batch {
+            messages: self
+                .operations
+                .iter()
+                .map(|operation| operation.to_message(aliases))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ValidationErrorDto {
+    pub message: String,
+    /// Errors of the single operations.
+    errors: Vec<OperationErrorDto>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OperationErrorDto {
+    index: usize,
+    message: String,
 }
 
 #[utoipa::path(
@@ -53,24 +152,29 @@ pub(crate) struct ExecuteAnimationDto {
     tag = "Data",
     summary = "Set text content",
     params(
-        ("instance" = String, Path, description = "Template instance name")
+        ("instance" = String, Path, description = "Template instance name"),
+        AckQueryParams
     ),
     request_body = SetTextDto,
     responses(
-        (status = 200, description = "Text updated"),
+        (status = 200, description = "Text updated", body = Option<CommandResultDto>),
     )
 )]
 pub(crate) async fn set_text(
     Path(instance): Path<String>,
+    Query(ack_params): Query<AckQueryParams>,
     Extension(server): Extension<Arc<WebsocketServer>>,
     Json(payload): Json<SetTextDto>,
 ) -> impl IntoResponse {
-    let message = ServerMessage::SetText {
-        id: &payload.id,
-        text: &payload.text,
-    };
-    send_message_with_optional_client(&instance, &server, message, payload.client).await;
-    StatusCode::OK
+    let message = payload.to_message();
+    send_message_to_target(
+        &instance,
+        &server,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
 }
 
 #[utoipa::path(
@@ -79,24 +183,29 @@ pub(crate) async fn set_text(
     tag = "Data",
     summary = "Add CSS class",
     params(
-        ("instance" = String, Path, description = "Template instance name")
+        ("instance" = String, Path, description = "Template instance name"),
+        AckQueryParams
     ),
     request_body = ManipulateClassDto,
     responses(
-        (status = 200, description = "Class added"),
+        (status = 200, description = "Class added", body = Option<CommandResultDto>),
     )
 )]
 pub(crate) async fn add_class(
     Path(instance): Path<String>,
+    Query(ack_params): Query<AckQueryParams>,
     Extension(server): Extension<Arc<WebsocketServer>>,
     Json(payload): Json<ManipulateClassDto>,
 ) -> impl IntoResponse {
-    let message = ServerMessage::AddClass {
-        id: &payload.id,
-        class: &payload.class,
-    };
-    send_message_with_optional_client(&instance, &server, message, payload.client).await;
-    StatusCode::OK
+    let message = payload.to_add_message();
+    send_message_to_target(
+        &instance,
+        &server,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
 }
 
 #[utoipa::path(
@@ -105,24 +214,29 @@ pub(crate) async fn add_class(
     tag = "Data",
     summary = "Remove CSS class",
     params(
-        ("instance" = String, Path, description = "Template instance name")
+        ("instance" = String, Path, description = "Template instance name"),
+        AckQueryParams
     ),
     request_body = ManipulateClassDto,
     responses(
-        (status = 200, description = "Class removed"),
+        (status = 200, description = "Class removed", body = Option<CommandResultDto>),
     )
 )]
 pub(crate) async fn remove_class(
     Path(instance): Path<String>,
+    Query(ack_params): Query<AckQueryParams>,
     Extension(server): Extension<Arc<WebsocketServer>>,
     Json(payload): Json<ManipulateClassDto>,
 ) -> impl IntoResponse {
-    let message = ServerMessage::RemoveClass {
-        id: &payload.id,
-        class: &payload.class,
-    };
-    send_message_with_optional_client(&instance, &server, message, payload.client).await;
-    StatusCode::OK
+    let message = payload.to_remove_message();
+    send_message_to_target(
+        &instance,
+        &server,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
 }
 
 #[utoipa::path(
@@ -131,24 +245,29 @@ pub(crate) async fn remove_class(
     tag = "Data",
     summary = "Execute animation",
     params(
-        ("instance" = String, Path, description = "Template instance name")
+        ("instance" = String, Path, description = "Template instance name"),
+        AckQueryParams
     ),
     request_body = ExecuteAnimationDto,
     responses(
-        (status = 200, description = "Animation command sent"),
+        (status = 200, description = "Animation command sent", body = Option<CommandResultDto>),
     )
 )]
 pub(crate) async fn execute_animation(
     Path(instance): Path<String>,
+    Query(ack_params): Query<AckQueryParams>,
     Extension(server): Extension<Arc<WebsocketServer>>,
     Json(payload): Json<ExecuteAnimationDto>,
 ) -> impl IntoResponse {
-    let message = ServerMessage::ExecuteAnimation {
-        animation_sequence: &payload.name,
-        queue_id: payload.queue.as_deref(),
-    };
-    send_message_with_optional_client(&instance, &server, message, payload.client).await;
-    StatusCode::OK
+    let message = payload.to_message();
+    send_message_to_target(
+        &instance,
+        &server,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
 }
 
 #[utoipa::path(
@@ -156,26 +275,33 @@ pub(crate) async fn execute_animation(
     path = "/api/instance/{instance}/data/image",
     tag = "Data",
     summary = "Set image source",
+    description = "The asset is either the name of an uploaded asset or an alias pointing to one. Elements set to an alias can be updated when the alias is pointed to another asset.",
     params(
-        ("instance" = String, Path, description = "Template instance name")
+        ("instance" = String, Path, description = "Template instance name"),
+        AckQueryParams
     ),
     request_body = SetImageSourceDto,
     responses(
-        (status = 200, description = "Image source updated"),
+        (status = 200, description = "Image source updated", body = Option<CommandResultDto>),
     )
 )]
 pub(crate) async fn set_image_source(
     Path(instance): Path<String>,
+    Query(ack_params): Query<AckQueryParams>,
     Extension(server): Extension<Arc<WebsocketServer>>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
     Json(payload): Json<SetImageSourceDto>,
 ) -> impl IntoResponse {
-    let message = ServerMessage::SetImageSource {
-        id: &payload.id,
-        asset: &payload.asset,
-        asset_source: payload.asset_source,
-    };
-    send_message_with_optional_client(&instance, &server, message, payload.client).await;
-    StatusCode::OK
+    let aliases = payload.resolve_aliases(&asset_store).await;
+    let message = payload.to_message(&aliases);
+    send_message_to_target(
+        &instance,
+        &server,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
 }
 
 #[utoipa::path(
@@ -184,39 +310,328 @@ pub(crate) async fn set_image_source(
     tag = "Data",
     summary = "Set CSS custom variable",
     params(
-        ("instance" = String, Path, description = "Template instance name")
+        ("instance" = String, Path, description = "Template instance name"),
+        AckQueryParams
     ),
     request_body = SetCustomVariableDto,
     responses(
-        (status = 200, description = "Custom variable updated"),
+        (status = 200, description = "Custom variable updated", body = Option<CommandResultDto>),
     )
 )]
 pub(crate) async fn set_custom_variable(
     Path(instance): Path<String>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Json(payload): Json<SetCustomVariableDto>,
+) -> impl IntoResponse {
+    let message = payload.to_message();
+    send_message_to_target(
+        &instance,
+        &server,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instance/{instance}/data/batch",
+    tag = "Data",
+    summary = "Apply several data operations at once",
+    description = "Validates all operations and sends them to the clients as one message which is applied within the same frame. If any operation is invalid, nothing is sent.",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        AckQueryParams
+    ),
+    request_body = BatchDto,
+    responses(
+        (status = 200, description = "Operations sent", body = Option<CommandResultDto>),
+        (status = 400, description = "No operations given or at least one operation is invalid", body = ValidationErrorDto),
+    )
+)]
+pub(crate) async fn apply_batch(
+    Path(instance): Path<String>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+    Json(payload): Json<BatchDto>,
+) -> impl IntoResponse {
+    if let Err(errors) = validate_operations(&payload.operations) {
+        return (StatusCode::BAD_REQUEST, Json(errors)).into_response();
+    }
+
+    let aliases = resolve_aliases(&payload.operations, &asset_store).await;
+    let message = payload.to_message(&aliases);
+    send_message_to_target(
+        &instance,
+        &server,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instances/data/text",
+    tag = "Data",
+    summary = "Set text content on several instances",
+    params(InstancesQueryParams, AckQueryParams),
+    request_body = SetTextDto,
+    responses(
+        (status = 200, description = "Number of clients per instance", body = MultiInstanceResultDto),
+        (status = 400, description = "Invalid instance pattern", body = String),
+    )
+)]
+pub(crate) async fn set_text_on_instances(
+    Query(instances_params): Query<InstancesQueryParams>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Json(payload): Json<SetTextDto>,
+) -> impl IntoResponse {
+    let message = payload.to_message();
+    send_message_to_instances(
+        &server,
+        &instances_params,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instances/data/class/add",
+    tag = "Data",
+    summary = "Add CSS class on several instances",
+    params(InstancesQueryParams, AckQueryParams),
+    request_body = ManipulateClassDto,
+    responses(
+        (status = 200, description = "Number of clients per instance", body = MultiInstanceResultDto),
+        (status = 400, description = "Invalid instance pattern", body = String),
+    )
+)]
+pub(crate) async fn add_class_on_instances(
+    Query(instances_params): Query<InstancesQueryParams>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Json(payload): Json<ManipulateClassDto>,
+) -> impl IntoResponse {
+    let message = payload.to_add_message();
+    send_message_to_instances(
+        &server,
+        &instances_params,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instances/data/class/remove",
+    tag = "Data",
+    summary = "Remove CSS class on several instances",
+    params(InstancesQueryParams, AckQueryParams),
+    request_body = ManipulateClassDto,
+    responses(
+        (status = 200, description = "Number of clients per instance", body = MultiInstanceResultDto),
+        (status = 400, description = "Invalid instance pattern", body = String),
+    )
+)]
+pub(crate) async fn remove_class_on_instances(
+    Query(instances_params): Query<InstancesQueryParams>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Json(payload): Json<ManipulateClassDto>,
+) -> impl IntoResponse {
+    let message = payload.to_remove_message();
+    send_message_to_instances(
+        &server,
+        &instances_params,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instances/data/animation",
+    tag = "Data",
+    summary = "Execute animation on several instances",
+    params(InstancesQueryParams, AckQueryParams),
+    request_body = ExecuteAnimationDto,
+    responses(
+        (status = 200, description = "Number of clients per instance", body = MultiInstanceResultDto),
+        (status = 400, description = "Invalid instance pattern", body = String),
+    )
+)]
+pub(crate) async fn execute_animation_on_instances(
+    Query(instances_params): Query<InstancesQueryParams>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Json(payload): Json<ExecuteAnimationDto>,
+) -> impl IntoResponse {
+    let message = payload.to_message();
+    send_message_to_instances(
+        &server,
+        &instances_params,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instances/data/image",
+    tag = "Data",
+    summary = "Set image source on several instances",
+    description = "The asset is either the name of an uploaded asset or an alias pointing to one.",
+    params(InstancesQueryParams, AckQueryParams),
+    request_body = SetImageSourceDto,
+    responses(
+        (status = 200, description = "Number of clients per instance", body = MultiInstanceResultDto),
+        (status = 400, description = "Invalid instance pattern", body = String),
+    )
+)]
+pub(crate) async fn set_image_source_on_instances(
+    Query(instances_params): Query<InstancesQueryParams>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+    Json(payload): Json<SetImageSourceDto>,
+) -> impl IntoResponse {
+    let aliases = payload.resolve_aliases(&asset_store).await;
+    let message = payload.to_message(&aliases);
+    send_message_to_instances(
+        &server,
+        &instances_params,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instances/data/custom-variable",
+    tag = "Data",
+    summary = "Set CSS custom variable on several instances",
+    params(InstancesQueryParams, AckQueryParams),
+    request_body = SetCustomVariableDto,
+    responses(
+        (status = 200, description = "Number of clients per instance", body = MultiInstanceResultDto),
+        (status = 400, description = "Invalid instance pattern", body = String),
+    )
+)]
+pub(crate) async fn set_custom_variable_on_instances(
+    Query(instances_params): Query<InstancesQueryParams>,
+    Query(ack_params): Query<AckQueryParams>,
     Extension(server): Extension<Arc<WebsocketServer>>,
     Json(payload): Json<SetCustomVariableDto>,
 ) -> impl IntoResponse {
-    let message = ServerMessage::SetCustomVariable {
-        name: &payload.name,
-        value: &payload.value,
-    };
-    send_message_with_optional_client(&instance, &server, message, payload.client).await;
+    let message = payload.to_message();
+    send_message_to_instances(
+        &server,
+        &instances_params,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instances/data/batch",
+    tag = "Data",
+    summary = "Apply several data operations at once on several instances",
+    description = "Validates all operations and sends them to the clients of every matching instance as one message. If any operation is invalid, nothing is sent.",
+    params(InstancesQueryParams, AckQueryParams),
+    request_body = BatchDto,
+    responses(
+        (status = 200, description = "Number of clients per instance", body = MultiInstanceResultDto),
+        (status = 400, description = "No operations given or at least one operation or the instance pattern is invalid", body = ValidationErrorDto),
+    )
+)]
+pub(crate) async fn apply_batch_on_instances(
+    Query(instances_params): Query<InstancesQueryParams>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+    Json(payload): Json<BatchDto>,
+) -> impl IntoResponse {
+    if let Err(errors) = validate_operations(&payload.operations) {
+        return (StatusCode::BAD_REQUEST, Json(errors)).into_response();
+    }
+
+    let aliases = resolve_aliases(&payload.operations, &asset_store).await;
+    let message = payload.to_message(&aliases);
+    send_message_to_instances(
+        &server,
+        &instances_params,
+        message,
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/instance/{instance}/data",
+    tag = "Data",
+    summary = "Clear stored instance data",
+    description = "Forgets the last known data of the instance so that it is neither replayed to newly connected clients nor restored after a restart. Connected clients are not changed.",
+    params(
+        ("instance" = String, Path, description = "Template instance name")
+    ),
+    responses(
+        (status = 200, description = "Instance data cleared"),
+    )
+)]
+pub(crate) async fn clear_instance_data(
+    Path(instance): Path<String>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+) -> impl IntoResponse {
+    server.clear_instance_data(&instance).await;
     StatusCode::OK
 }
 
-async fn send_message_with_optional_client(
-    instance: &str,
-    server: &WebsocketServer,
-    message: ServerMessage<'_>,
-    client: Option<usize>,
-) {
-    if let Some(client_id) = client {
-        server
-            .send_message_to_instance_client(instance, client_id, &message)
-            .await;
+/// A batch without operations would be sent to the clients without changing anything.
+pub(crate) fn validate_operations(operations: &[DataOperation]) -> Result<(), ValidationErrorDto> {
+    if operations.is_empty() {
+        return Err(ValidationErrorDto {
+            message: String::from("At least one operation must be given."),
+            errors: Vec::new(),
+        });
+    }
+    let errors: Vec<OperationErrorDto> = operations
+        .iter()
+        .enumerate()
+        .filter_map(|(index, operation)| {
+            operation
+                .validate()
+                .err()
+                .map(|message| OperationErrorDto { index, message })
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
     } else {
-        server
-            .send_message_to_instance_clients(instance, &message)
-            .await;
+        Err(ValidationErrorDto {
+            message: String::from("At least one operation is invalid."),
+            errors,
+        })
     }
 }
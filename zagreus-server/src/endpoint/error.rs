@@ -0,0 +1,91 @@
+use crate::websocket::server::WebsocketServer;
+use crate::websocket::template_error::TemplateError;
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TemplateErrorDto {
+    client: usize,
+    message: String,
+    stack: String,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    count: u64,
+}
+
+impl From<TemplateError> for TemplateErrorDto {
+    fn from(error: TemplateError) -> Self {
+        TemplateErrorDto {
+            client: error.client_id,
+            message: error.message,
+            stack: error.stack,
+            first_seen: error.first_seen,
+            last_seen: error.last_seen,
+            count: error.count,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct TemplateErrorQueryParams {
+    /// Only include the errors of this client.
+    client: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/instance/{instance}/errors",
+    tag = "Instance",
+    summary = "List template errors of an instance",
+    description = "Errors reported by the clients of the instance, including clients that disconnected since. An error that a client reports repeatedly is listed once with the number of times it occurred. The most recently reported error comes last.",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        TemplateErrorQueryParams
+    ),
+    responses(
+        (status = 200, description = "Template errors", body = Vec<TemplateErrorDto>),
+    )
+)]
+pub(crate) async fn get_template_errors(
+    Path(instance): Path<String>,
+    Query(params): Query<TemplateErrorQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+) -> impl IntoResponse {
+    let errors: Vec<TemplateErrorDto> = server
+        .get_template_errors(&instance)
+        .await
+        .into_iter()
+        .filter(|error| params.client.is_none_or(|client| error.client_id == client))
+        .map(TemplateErrorDto::from)
+        .collect();
+    Json(errors)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/instance/{instance}/errors",
+    tag = "Instance",
+    summary = "Clear template errors of an instance",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        TemplateErrorQueryParams
+    ),
+    responses(
+        (status = 200, description = "Template errors cleared"),
+    )
+)]
+pub(crate) async fn clear_template_errors(
+    Path(instance): Path<String>,
+    Query(params): Query<TemplateErrorQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+) -> impl IntoResponse {
+    server.clear_template_errors(&instance, params.client).await;
+    StatusCode::OK
+}
@@ -0,0 +1,62 @@
+use crate::event::ServerEvent;
+use crate::websocket::server::WebsocketServer;
+use axum::Extension;
+use axum::extract::Query;
+use axum::response::IntoResponse;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct EventQueryParams {
+    /// Only stream events of this instance.
+    instance: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    tag = "Event",
+    summary = "Stream server events",
+    description = "Server-sent events stream of connected and disconnected clients, state changes, template errors and commands sent to the clients. The SSE event name is the type of the event. If the stream falls behind, a `lagged` event with the number of skipped events is sent instead.",
+    params(EventQueryParams),
+    responses(
+        (status = 200, description = "Stream of server events", body = ServerEvent, content_type = "text/event-stream"),
+    )
+)]
+pub(crate) async fn get_events(
+    Query(params): Query<EventQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+) -> impl IntoResponse {
+    let stream =
+        BroadcastStream::new(server.subscribe_events()).filter_map(move |result| match result {
+            Ok(event) => {
+                if params
+                    .instance
+                    .as_ref()
+                    .is_some_and(|instance| instance != event.get_instance())
+                {
+                    return None;
+                }
+                match Event::default().event(event.get_type()).json_data(&*event) {
+                    Ok(sse_event) => Some(Ok::<Event, Infallible>(sse_event)),
+                    Err(err) => {
+                        error!("Could not serialize server event: {}.", err);
+                        None
+                    }
+                }
+            }
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                warn!("Event stream fell behind and skipped {} events.", skipped);
+                Some(Ok(Event::default()
+                    .event("lagged")
+                    .data(skipped.to_string())))
+            }
+        });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,259 @@
+use crate::websocket::queue::QueueStatistics;
+use crate::websocket::server::WebsocketServer;
+use crate::websocket::target::{ClientSelector, normalize_tags};
+use axum::extract::{Path, Query};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct InstanceDto {
+    name: String,
+    client_count: usize,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClientDto {
+    id: usize,
+    name: Option<String>,
+    tags: Vec<String>,
+    connected_at: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    remote_address: String,
+    user_agent: Option<String>,
+    latency_millis: Option<u64>,
+    send_queue: SendQueueDto,
+    runtime: Option<ClientRuntimeDto>,
+    version_mismatch: bool,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SendQueueDto {
+    depth: usize,
+    capacity: usize,
+    dropped: u64,
+    coalesced: u64,
+}
+
+impl From<QueueStatistics> for SendQueueDto {
+    fn from(statistics: QueueStatistics) -> Self {
+        SendQueueDto {
+            depth: statistics.depth,
+            capacity: statistics.capacity,
+            dropped: statistics.dropped,
+            coalesced: statistics.coalesced,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClientRuntimeDto {
+    runtime_version: String,
+    protocol_version: u32,
+    container_width: u32,
+    container_height: u32,
+    supported_messages: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/instance",
+    tag = "Instance",
+    summary = "List instances with connected clients",
+    responses(
+        (status = 200, description = "Instances with at least one connected client", body = Vec<InstanceDto>),
+    )
+)]
+pub(crate) async fn get_instances(
+    Extension(server): Extension<Arc<WebsocketServer>>,
+) -> impl IntoResponse {
+    let mut instances: Vec<InstanceDto> = server
+        .get_instance_client_counts()
+        .await
+        .into_iter()
+        .map(|(name, client_count)| InstanceDto { name, client_count })
+        .collect();
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(instances)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/instance/{instance}/client",
+    tag = "Instance",
+    summary = "List connected clients of an instance",
+    params(
+        ("instance" = String, Path, description = "Template instance name")
+    ),
+    responses(
+        (status = 200, description = "Connected clients", body = Vec<ClientDto>),
+    )
+)]
+pub(crate) async fn get_clients(
+    Path(instance): Path<String>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+) -> impl IntoResponse {
+    let mut clients = Vec::new();
+    server
+        .iterate_connections(&instance, |connection| {
+            let connection_info = connection.get_connection_info();
+            let hello = connection.get_hello();
+            clients.push(ClientDto {
+                id: connection.client_id,
+                name: connection.get_name().map(String::from),
+                tags: connection.get_tags().into_iter().collect(),
+                connected_at: connection.get_connected_at(),
+                last_seen: connection.get_last_seen(),
+                remote_address: connection_info.remote_address.to_string(),
+                user_agent: connection_info
+                    .user_agent
+                    .clone()
+                    .or_else(|| hello.map(|hello| hello.user_agent.clone())),
+                latency_millis: connection
+                    .get_latency()
+                    .map(|latency| latency.as_millis() as u64),
+                send_queue: SendQueueDto::from(connection.get_queue_statistics()),
+                runtime: hello.map(|hello| {
+                    let mut supported_messages: Vec<String> =
+                        hello.supported_messages.iter().cloned().collect();
+                    supported_messages.sort();
+                    ClientRuntimeDto {
+                        runtime_version: hello.runtime_version.clone(),
+                        protocol_version: hello.protocol_version,
+                        container_width: hello.container.width,
+                        container_height: hello.container.height,
+                        supported_messages,
+                    }
+                }),
+                version_mismatch: connection.has_version_mismatch(),
+            });
+        })
+        .await;
+    clients.sort_by_key(|client| client.id);
+    Json(clients)
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RunningAnimationDto {
+    client: usize,
+    sequence: String,
+    queue_id: Option<String>,
+    started_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/instance/{instance}/animation",
+    tag = "Instance",
+    summary = "List running animation sequences of an instance",
+    description = "Animation sequences that clients reported as started and not yet finished or cancelled. Each animation queue of a client runs at most one sequence at a time.",
+    params(
+        ("instance" = String, Path, description = "Template instance name")
+    ),
+    responses(
+        (status = 200, description = "Running animation sequences", body = Vec<RunningAnimationDto>),
+    )
+)]
+pub(crate) async fn get_running_animations(
+    Path(instance): Path<String>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+) -> impl IntoResponse {
+    let mut animations = Vec::new();
+    server
+        .iterate_client_states(&instance, |state| {
+            animations.extend(state.get_running_animations().iter().map(|animation| {
+                RunningAnimationDto {
+                    client: state.client_id,
+                    sequence: animation.sequence.clone(),
+                    queue_id: animation.queue_id.clone(),
+                    started_at: animation.started_at,
+                }
+            }));
+        })
+        .await;
+    animations.sort_by_key(|animation| (animation.client, animation.started_at));
+    Json(animations)
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClientTagsDto {
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AssignedTagsDto {
+    /// Connected clients whose tags were replaced.
+    clients: Vec<usize>,
+}
+
+/// Exactly one of id and name selects the client, so that names that look like ids are not
+/// mistaken for them.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ClientTagsQueryParams {
+    /// Id of a connected client.
+    id: Option<usize>,
+    /// Name of a client, which does not have to be connected.
+    name: Option<String>,
+}
+
+impl ClientTagsQueryParams {
+    fn get_selector(self) -> Option<ClientSelector> {
+        match (self.id, self.name) {
+            (Some(client_id), None) => Some(ClientSelector::Id(client_id)),
+            (None, Some(name)) => Some(ClientSelector::Name(name)),
+            _ => None,
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/instance/{instance}/client/tags",
+    tag = "Instance",
+    summary = "Replace the tags of a client",
+    description = "Replaces the tags the client declared when connecting. Tags of named clients are remembered and assigned again when the client reconnects, so they can also be assigned to a name before the client connects.",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        ClientTagsQueryParams
+    ),
+    request_body = ClientTagsDto,
+    responses(
+        (status = 200, description = "Tags assigned", body = AssignedTagsDto),
+        (status = 400, description = "Invalid tag or not exactly one of id and name given", body = String),
+        (status = 404, description = "No connected client with this id"),
+    )
+)]
+pub(crate) async fn set_client_tags(
+    Path(instance): Path<String>,
+    Query(params): Query<ClientTagsQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Json(payload): Json<ClientTagsDto>,
+) -> impl IntoResponse {
+    let tags = match normalize_tags(&payload.tags) {
+        Ok(tags) => tags,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let Some(selector) = params.get_selector() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Exactly one of id and name has to be given.",
+        )
+            .into_response();
+    };
+    let clients = server.set_client_tags(&instance, &selector, tags).await;
+    if clients.is_empty() && selector.get_single_id().is_some() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    Json(AssignedTagsDto { clients }).into_response()
+}
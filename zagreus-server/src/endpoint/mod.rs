@@ -2,10 +2,16 @@ use crate::ZAGREUS_VERSION;
 use axum::response::IntoResponse;
 
 mod asset;
+mod control;
 mod data;
+mod error;
+mod event;
+mod instance;
 pub mod openapi;
+mod preset;
 pub mod routes;
 mod state;
+mod target;
 mod websocket;
 
 #[utoipa::path(
@@ -7,8 +7,11 @@ use axum::response::IntoResponse;
     tags(
         (name = "General", description = "General API endpoints"),
         (name = "Asset", description = "Asset management"),
+        (name = "Instance", description = "Instances and connected clients"),
         (name = "Data", description = "Template data manipulation"),
-        (name = "State", description = "State management")
+        (name = "Preset", description = "Named lists of data operations"),
+        (name = "State", description = "State management"),
+        (name = "Event", description = "Server activity for control applications")
     ),
     info(
         title = "Zagreus Server API",
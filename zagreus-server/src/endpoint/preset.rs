@@ -0,0 +1,195 @@
+use crate::WebsocketServer;
+use crate::data::asset::AssetStore;
+use crate::data::operation::{DataOperation, resolve_aliases};
+use crate::data::preset::{Preset, PresetStore};
+use crate::endpoint::data::{ValidationErrorDto, validate_operations};
+use crate::websocket::message::ServerMessage;
+use axum::Json;
+use axum::extract::{Extension, Path};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde_json::json;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PresetDto {
+    name: String,
+    operations: Vec<DataOperation>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SavePresetDto {
+    operations: Vec<DataOperation>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/instance/{instance}/preset",
+    tag = "Preset",
+    summary = "List presets",
+    params(
+        ("instance" = String, Path, description = "Template instance name")
+    ),
+    responses(
+        (status = 200, description = "Presets of the instance", body = Vec<PresetDto>),
+    )
+)]
+pub(crate) async fn get_presets(
+    Path(instance): Path<String>,
+    Extension(preset_store): Extension<Arc<PresetStore>>,
+) -> impl IntoResponse {
+    let presets: Vec<PresetDto> = preset_store
+        .get_presets(&instance)
+        .await
+        .into_iter()
+        .map(|(name, preset)| PresetDto {
+            name,
+            operations: preset.operations,
+        })
+        .collect();
+    Json(presets)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/instance/{instance}/preset/{name}",
+    tag = "Preset",
+    summary = "Get preset",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        ("name" = String, Path, description = "Preset name")
+    ),
+    responses(
+        (status = 200, description = "Preset", body = PresetDto),
+        (status = 404, description = "Preset not found"),
+    )
+)]
+pub(crate) async fn get_preset(
+    Path((instance, name)): Path<(String, String)>,
+    Extension(preset_store): Extension<Arc<PresetStore>>,
+) -> impl IntoResponse {
+    match preset_store.get_preset(&instance, &name).await {
+        Some(preset) => Json(PresetDto {
+            name,
+            operations: preset.operations,
+        })
+        .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/instance/{instance}/preset/{name}",
+    tag = "Preset",
+    summary = "Create or replace preset",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        ("name" = String, Path, description = "Preset name")
+    ),
+    request_body = SavePresetDto,
+    responses(
+        (status = 200, description = "Preset saved"),
+        (status = 400, description = "No operations given or at least one operation is invalid", body = ValidationErrorDto),
+        (status = 500, description = "Preset could not be stored", body = String),
+    )
+)]
+pub(crate) async fn save_preset(
+    Path((instance, name)): Path<(String, String)>,
+    Extension(preset_store): Extension<Arc<PresetStore>>,
+    Json(payload): Json<SavePresetDto>,
+) -> impl IntoResponse {
+    if let Err(errors) = validate_operations(&payload.operations) {
+        return (StatusCode::BAD_REQUEST, Json(errors)).into_response();
+    }
+
+    let preset = Preset {
+        operations: payload.operations,
+    };
+    match preset_store.save_preset(&instance, &name, preset).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(err) => {
+            error!("Could not save preset {}: {:#}.", name, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!("Could not save preset.")),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/instance/{instance}/preset/{name}",
+    tag = "Preset",
+    summary = "Delete preset",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        ("name" = String, Path, description = "Preset name")
+    ),
+    responses(
+        (status = 200, description = "Preset deleted"),
+        (status = 404, description = "Preset not found"),
+        (status = 500, description = "Preset could not be deleted", body = String),
+    )
+)]
+pub(crate) async fn delete_preset(
+    Path((instance, name)): Path<(String, String)>,
+    Extension(preset_store): Extension<Arc<PresetStore>>,
+) -> impl IntoResponse {
+    match preset_store.delete_preset(&instance, &name).await {
+        Ok(true) => StatusCode::OK.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            error!("Could not delete preset {}: {:#}.", name, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!("Could not delete preset.")),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instance/{instance}/preset/{name}/apply",
+    tag = "Preset",
+    summary = "Apply preset",
+    description = "Sends all operations of the preset to the clients of the instance as one batch.",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        ("name" = String, Path, description = "Preset name")
+    ),
+    responses(
+        (status = 200, description = "Preset applied"),
+        (status = 404, description = "Preset not found"),
+    )
+)]
+pub(crate) async fn apply_preset(
+    Path((instance, name)): Path<(String, String)>,
+    Extension(preset_store): Extension<Arc<PresetStore>>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Extension(asset_store): Extension<Arc<AssetStore>>,
+) -> impl IntoResponse {
+    let Some(preset) = preset_store.get_preset(&instance, &name).await else {
+        return StatusCode::NOT_FOUND;
+    };
+    // aliases are resolved when the preset is applied, so it shows what they point to now
+    let aliases = resolve_aliases(&preset.operations, &asset_store).await;
+    let message = ServerMessage::Batch {
+        messages: preset
+            .operations
+            .iter()
+            .map(|operation| operation.to_message(&aliases))
+            .collect(),
+    };
+    server
+        .send_message_to_instance_clients(&instance, &message)
+        .await;
+    StatusCode::OK
+}
@@ -1,6 +1,7 @@
 use axum::Router;
 use axum::body::Body;
 use axum::error_handling::HandleErrorLayer;
+use axum::extract::DefaultBodyLimit;
 use axum::http::uri::InvalidUri;
 use axum::http::{Request, StatusCode, Uri};
 use std::sync::Arc;
@@ -12,11 +13,16 @@ use utoipa_axum::routes;
 
 use crate::config::ZagreusServerConfig;
 use crate::controller::ServerController;
+use crate::data::asset::AssetStore;
+use crate::data::asset::rendition::RenditionCache;
+use crate::data::preset::PresetStore;
 use crate::endpoint;
 use crate::endpoint::websocket::ws_handler;
 use crate::fs::get_assets_folder;
 use crate::websocket::server::WebsocketServer;
 
+const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
+
 // e.g. rewrite /static/template/my-template to /static/template/my-template/
 // TODO parse url better (what if there are multiple dots in the asset name?)
 // TODO still necessary?
@@ -49,21 +55,84 @@ pub fn get_router(
     configuration: &ZagreusServerConfig,
     ws_server: Arc<WebsocketServer>,
     server_controller: Arc<ServerController>,
+    asset_store: Arc<AssetStore>,
 ) -> anyhow::Result<Router> {
     let assets_folder = get_assets_folder(&configuration.data_folder)?;
+    let preset_store = Arc::new(PresetStore::load(&configuration.data_folder)?);
+    let upload_config = Arc::new(configuration.asset_upload.clone());
+    let rendition_cache = Arc::new(RenditionCache::new(
+        &assets_folder,
+        &configuration.asset_renditions,
+    )?);
+    // leave room for the other fields and the boundaries of the multipart request
+    let upload_router = OpenApiRouter::new()
+        .routes(routes!(
+            crate::endpoint::asset::get_assets,
+            crate::endpoint::asset::upload_asset
+        ))
+        .layer(DefaultBodyLimit::max(
+            upload_config.max_size_bytes + MULTIPART_OVERHEAD_BYTES,
+        ));
     let (api_router, openapi) = OpenApiRouter::with_openapi(endpoint::openapi::ApiDoc::openapi())
         .routes(routes!(crate::endpoint::get_server_version))
-        .routes(routes!(crate::endpoint::asset::upload_asset))
+        .merge(upload_router)
+        .routes(routes!(
+            crate::endpoint::asset::get_asset,
+            crate::endpoint::asset::delete_asset
+        ))
+        .routes(routes!(crate::endpoint::asset::get_asset_aliases))
+        .routes(routes!(
+            crate::endpoint::asset::get_asset_alias,
+            crate::endpoint::asset::set_asset_alias,
+            crate::endpoint::asset::delete_asset_alias
+        ))
+        .routes(routes!(crate::endpoint::instance::get_instances))
+        .routes(routes!(crate::endpoint::instance::get_clients))
+        .routes(routes!(crate::endpoint::instance::set_client_tags))
+        .routes(routes!(crate::endpoint::instance::get_running_animations))
+        .routes(routes!(
+            crate::endpoint::error::get_template_errors,
+            crate::endpoint::error::clear_template_errors
+        ))
         .routes(routes!(crate::endpoint::data::set_text))
         .routes(routes!(crate::endpoint::data::add_class))
         .routes(routes!(crate::endpoint::data::remove_class))
         .routes(routes!(crate::endpoint::data::execute_animation))
         .routes(routes!(crate::endpoint::data::set_image_source))
         .routes(routes!(crate::endpoint::data::set_custom_variable))
+        .routes(routes!(crate::endpoint::data::apply_batch))
+        .routes(routes!(crate::endpoint::data::clear_instance_data))
+        .routes(routes!(crate::endpoint::data::set_text_on_instances))
+        .routes(routes!(crate::endpoint::data::add_class_on_instances))
+        .routes(routes!(crate::endpoint::data::remove_class_on_instances))
+        .routes(routes!(
+            crate::endpoint::data::execute_animation_on_instances
+        ))
+        .routes(routes!(
+            crate::endpoint::data::set_image_source_on_instances
+        ))
+        .routes(routes!(
+            crate::endpoint::data::set_custom_variable_on_instances
+        ))
+        .routes(routes!(crate::endpoint::data::apply_batch_on_instances))
+        .routes(routes!(crate::endpoint::preset::get_presets))
+        .routes(routes!(
+            crate::endpoint::preset::get_preset,
+            crate::endpoint::preset::save_preset,
+            crate::endpoint::preset::delete_preset
+        ))
+        .routes(routes!(crate::endpoint::preset::apply_preset))
         .routes(routes!(crate::endpoint::state::get_state))
         .routes(routes!(crate::endpoint::state::set_state))
-        .layer(axum::extract::Extension(ws_server))
+        .routes(routes!(crate::endpoint::state::set_state_on_instances))
+        .routes(routes!(crate::endpoint::state::wait_for_state))
+        .routes(routes!(crate::endpoint::event::get_events))
+        .layer(axum::extract::Extension(ws_server.clone()))
         .layer(axum::extract::Extension(assets_folder.clone()))
+        .layer(axum::extract::Extension(preset_store))
+        .layer(axum::extract::Extension(asset_store.clone()))
+        .layer(axum::extract::Extension(upload_config))
+        .layer(axum::extract::Extension(rendition_cache.clone()))
         .split_for_parts();
 
     let mut router = Router::new()
@@ -78,12 +147,17 @@ pub fn get_router(
         )
         .layer(axum::extract::Extension(openapi));
 
-    let assets_router = Router::new().nest_service(
-        "/assets",
-        axum::routing::get_service(ServeDir::new(&assets_folder)).handle_error(|err| async move {
-            error!("error occurred when serving assets: {}.", err)
-        }),
-    );
+    let assets_router = Router::new()
+        .nest_service(
+            "/assets",
+            axum::routing::get_service(ServeDir::new(&assets_folder)).handle_error(
+                |err| async move { error!("error occurred when serving assets: {}.", err) },
+            ),
+        )
+        .layer(axum::middleware::map_request(
+            endpoint::asset::map_rendition_url,
+        ))
+        .layer(axum::extract::Extension(rendition_cache));
     router = router.merge(assets_router);
 
     let static_router = Router::new().nest(
@@ -123,7 +197,13 @@ pub fn get_router(
     // route for websocket router
     let websocket_router = Router::new()
         .route("/ws/instance/{instance}", axum::routing::get(ws_handler))
-        .layer(axum::extract::Extension(server_controller));
+        .route(
+            "/ws/control",
+            axum::routing::get(endpoint::control::control_ws_handler),
+        )
+        .layer(axum::extract::Extension(server_controller))
+        .layer(axum::extract::Extension(ws_server))
+        .layer(axum::extract::Extension(asset_store));
     router = router.merge(websocket_router);
 
     let middleware_stack = ServiceBuilder::new()
@@ -1,18 +1,39 @@
+use crate::endpoint::target::{
+    AckQueryParams, ClientSelectorQueryParams, CommandResultDto, InstancesQueryParams,
+    MultiInstanceResultDto, send_message_to_instances, send_message_to_target,
+};
 use crate::websocket::message::ServerMessage;
 use crate::websocket::server::WebsocketServer;
+use crate::websocket::target::ClientSelector;
 use axum::extract::{Path, Query};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::{Extension, Json};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use utoipa::ToSchema;
+use std::time::Duration;
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_STATE_WAIT_TIMEOUT_MILLIS: u64 = 10_000;
+const MAX_STATE_WAIT_TIMEOUT_MILLIS: u64 = 60_000;
 
 #[derive(Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct GetStateDto {
     clients_unset: Vec<usize>,
     clients_set: Vec<GetStateItemDto>,
+    /// Names of the listed clients that declared one.
+    client_names: HashMap<usize, String>,
+    /// Clients whose reported value differs from the value the server last set for them.
+    drift: Vec<StateDriftDto>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StateDriftDto {
+    client: usize,
+    desired: Option<String>,
+    reported: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, ToSchema)]
@@ -25,6 +46,8 @@ pub(crate) struct GetStateItemDto {
 #[derive(Deserialize)]
 pub(crate) struct GetStateQueryParams {
     name: String,
+    #[serde(flatten)]
+    selector: ClientSelectorQueryParams,
 }
 
 #[utoipa::path(
@@ -32,12 +55,15 @@ pub(crate) struct GetStateQueryParams {
     path = "/api/instance/{instance}/state",
     tag = "State",
     summary = "Get state values for connected clients",
+    description = "Groups the clients by the value they reported for the state and lists the clients whose reported value differs from the value the server last set for them.",
     params(
         ("instance" = String, Path, description = "Template instance name"),
-        ("name" = String, Query, description = "State name to resolve")
+        ("name" = String, Query, description = "State name to resolve"),
+        ClientSelectorQueryParams
     ),
     responses(
         (status = 200, description = "State retrieved", body = GetStateDto),
+        (status = 400, description = "Invalid client selector", body = String),
     )
 )]
 pub(crate) async fn get_state(
@@ -45,11 +71,37 @@ pub(crate) async fn get_state(
     params: Query<GetStateQueryParams>,
     Extension(server): Extension<Arc<WebsocketServer>>,
 ) -> impl IntoResponse {
+    match params.selector.to_selector() {
+        Ok(selector) => {
+            Json(collect_state(&server, &instance, &params.name, selector.as_ref()).await)
+                .into_response()
+        }
+        Err(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+    }
+}
+
+/// Groups the selected clients of the instance by their value of the given state.
+pub(crate) async fn collect_state(
+    server: &WebsocketServer,
+    instance: &str,
+    name: &str,
+    selector: Option<&ClientSelector>,
+) -> GetStateDto {
     let mut grouped_clients: HashMap<String, HashSet<usize>> = HashMap::new();
     let mut clients_unset = Vec::new();
+    let mut client_names = HashMap::new();
+    let mut drift = Vec::new();
     server
-        .iterate_client_states(&instance, |state| {
-            if let Some(state_value) = state.get_state(&params.name) {
+        .iterate_desired_state(instance, name, |connection, desired| {
+            if selector.is_some_and(|selector| !selector.matches(connection)) {
+                return;
+            }
+            if let Some(client_name) = connection.get_name() {
+                client_names.insert(connection.client_id, client_name.to_string());
+            }
+            let state = connection.get_client_state();
+            let reported = state.get_state(name).map(String::as_str);
+            if let Some(state_value) = reported {
                 grouped_clients
                     .entry(state_value.to_string())
                     .or_default()
@@ -57,8 +109,16 @@ pub(crate) async fn get_state(
             } else {
                 clients_unset.push(state.client_id);
             }
+            if let Some(desired) = desired.filter(|desired| *desired != reported) {
+                drift.push(StateDriftDto {
+                    client: state.client_id,
+                    desired: desired.map(String::from),
+                    reported: reported.map(String::from),
+                });
+            }
         })
         .await;
+    drift.sort_by_key(|drift| drift.client);
     let client_set_items = grouped_clients
         .into_iter()
         .map(|(state_value, clients)| GetStateItemDto {
@@ -66,10 +126,12 @@ pub(crate) async fn get_state(
             value: state_value,
         })
         .collect::<Vec<GetStateItemDto>>();
-    Json(GetStateDto {
+    GetStateDto {
         clients_unset,
         clients_set: client_set_items,
-    })
+        client_names,
+        drift,
+    }
 }
 
 #[derive(Deserialize, Serialize, ToSchema)]
@@ -77,32 +139,233 @@ pub(crate) async fn get_state(
 pub(crate) struct SetStateDto {
     name: String,
     value: Option<String>,
+    client: Option<ClientSelector>,
+}
+
+impl SetStateDto {
+    fn to_message(&self) -> ServerMessage<'_> {
+        ServerMessage::SetState {
+            name: &self.name,
+            value: self.value.as_deref(),
+        }
+    }
 }
 
 #[utoipa::path(
     post,
     path = "/api/instance/{instance}/state",
     tag = "State",
-    summary = "Set state for all clients",
+    summary = "Set state for all or the selected clients",
+    description = "The server keeps the state and applies it again to clients that reconnect. State for clients selected by name or tags is kept per client and overrides the state of the whole instance, state for clients selected by id is not kept as ids change on every reconnect.",
     params(
-        ("instance" = String, Path, description = "Template instance name")
+        ("instance" = String, Path, description = "Template instance name"),
+        AckQueryParams
     ),
     request_body = SetStateDto,
     responses(
-        (status = 200, description = "State update sent"),
+        (status = 200, description = "State update sent", body = Option<CommandResultDto>),
     )
 )]
 pub(crate) async fn set_state(
     Path(instance): Path<String>,
+    Query(ack_params): Query<AckQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+    Json(payload): Json<SetStateDto>,
+) -> impl IntoResponse {
+    send_message_to_target(
+        &instance,
+        &server,
+        payload.to_message(),
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/instances/state",
+    tag = "State",
+    summary = "Set state on several instances",
+    params(InstancesQueryParams, AckQueryParams),
+    request_body = SetStateDto,
+    responses(
+        (status = 200, description = "Number of clients per instance", body = MultiInstanceResultDto),
+        (status = 400, description = "Invalid instance pattern", body = String),
+    )
+)]
+pub(crate) async fn set_state_on_instances(
+    Query(instances_params): Query<InstancesQueryParams>,
+    Query(ack_params): Query<AckQueryParams>,
     Extension(server): Extension<Arc<WebsocketServer>>,
     Json(payload): Json<SetStateDto>,
 ) -> impl IntoResponse {
-    let message = ServerMessage::SetState {
-        name: &payload.name,
-        value: payload.value.as_deref(),
+    send_message_to_instances(
+        &server,
+        &instances_params,
+        payload.to_message(),
+        payload.client.as_ref(),
+        &ack_params,
+    )
+    .await
+}
+
+#[derive(Deserialize, Serialize, ToSchema, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum StateWaitMode {
+    /// Every selected client reports the value, there has to be at least one client.
+    #[default]
+    All,
+    /// At least one selected client reports the value.
+    Any,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct WaitStateQueryParams {
+    /// State name to wait for.
+    name: String,
+    /// Value to wait for, without a value the clients have to report the state as unset.
+    value: Option<String>,
+    /// Whether all or any of the selected clients have to report the value (default all).
+    #[param(inline)]
+    mode: Option<StateWaitMode>,
+    /// Maximum time in milliseconds to wait (default 10000, at most 60000).
+    timeout: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WaitStateDto {
+    reached: bool,
+    /// Selected clients that report the value.
+    clients_reached: Vec<usize>,
+    /// Selected clients that do not report the value yet.
+    clients_pending: Vec<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/instance/{instance}/state/wait",
+    tag = "State",
+    summary = "Wait until clients report a state value",
+    description = "Blocks until all (or any) of the selected clients report the value for the state or the timeout elapsed. The response tells whether the value was reached and which clients report it, so scripts do not need to poll the state.",
+    params(
+        ("instance" = String, Path, description = "Template instance name"),
+        WaitStateQueryParams,
+        ClientSelectorQueryParams
+    ),
+    responses(
+        (status = 200, description = "Value reached or timeout elapsed", body = WaitStateDto),
+        (status = 400, description = "Invalid client selector", body = String),
+        (status = 404, description = "No client of the instance connected yet and no data was sent to it"),
+    )
+)]
+pub(crate) async fn wait_for_state(
+    Path(instance): Path<String>,
+    Query(params): Query<WaitStateQueryParams>,
+    Query(selector_params): Query<ClientSelectorQueryParams>,
+    Extension(server): Extension<Arc<WebsocketServer>>,
+) -> impl IntoResponse {
+    let selector = match selector_params.to_selector() {
+        Ok(selector) => selector,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
     };
+    let timeout_millis = params
+        .timeout
+        .unwrap_or(DEFAULT_STATE_WAIT_TIMEOUT_MILLIS)
+        .min(MAX_STATE_WAIT_TIMEOUT_MILLIS);
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout_millis);
+    let mode = params.mode.unwrap_or_default();
+
+    // subscribe first so that no change between looking at the states and waiting is missed
+    let Some(mut changes) = server.subscribe_client_state_changes(&instance).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    loop {
+        changes.mark_unchanged();
+        let result = check_state(
+            &server,
+            &instance,
+            &params.name,
+            params.value.as_deref(),
+            mode,
+            selector.as_ref(),
+        )
+        .await;
+        if result.reached {
+            return Json(result).into_response();
+        }
+        tokio::select! {
+            changed = changes.changed() => {
+                if changed.is_err() {
+                    return Json(result).into_response();
+                }
+            }
+            _ = tokio::time::sleep_until(deadline) => return Json(result).into_response(),
+        }
+    }
+}
+
+async fn check_state(
+    server: &WebsocketServer,
+    instance: &str,
+    name: &str,
+    value: Option<&str>,
+    mode: StateWaitMode,
+    selector: Option<&ClientSelector>,
+) -> WaitStateDto {
+    let mut clients_reached = Vec::new();
+    let mut clients_pending = Vec::new();
     server
-        .send_message_to_instance_clients(&instance, &message)
+        .iterate_connections(instance, |connection| {
+            if selector.is_some_and(|selector| !selector.matches(connection)) {
+                return;
+            }
+            let state = connection.get_client_state();
+            if state.get_state(name).map(String::as_str) == value {
+                clients_reached.push(state.client_id);
+            } else {
+                clients_pending.push(state.client_id);
+            }
+        })
         .await;
-    StatusCode::OK
+    clients_reached.sort();
+    clients_pending.sort();
+    WaitStateDto {
+        reached: is_state_reached(mode, clients_reached.len(), clients_pending.len()),
+        clients_reached,
+        clients_pending,
+    }
+}
+
+fn is_state_reached(mode: StateWaitMode, reached: usize, pending: usize) -> bool {
+    match mode {
+        StateWaitMode::All => reached > 0 && pending == 0,
+        StateWaitMode::Any => reached > 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_state_reached() {
+        assert!(is_state_reached(StateWaitMode::All, 2, 0));
+        assert!(!is_state_reached(StateWaitMode::All, 2, 1));
+        assert!(!is_state_reached(StateWaitMode::All, 0, 0));
+        assert!(is_state_reached(StateWaitMode::Any, 1, 3));
+        assert!(!is_state_reached(StateWaitMode::Any, 0, 3));
+    }
+
+    #[test]
+    fn test_deserialize_wait_params() {
+        let uri = "/state/wait?name=phase&value=ready&mode=any&timeout=500"
+            .parse()
+            .unwrap();
+        let Query(params) = Query::<WaitStateQueryParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.mode, Some(StateWaitMode::Any));
+        assert_eq!(params.timeout, Some(500));
+    }
 }
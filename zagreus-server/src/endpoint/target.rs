@@ -0,0 +1,301 @@
+//! Targeting of commands: which clients of an instance receive them and whether the caller
+//! waits for their acknowledgements.
+
+use crate::WebsocketServer;
+use crate::websocket::ack::{AckStatus, ClientAck};
+use crate::websocket::message::ServerMessage;
+use crate::websocket::target::{ClientGroup, ClientSelector, InstanceSelector, parse_tags};
+use axum::Json;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use std::time::Duration;
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_ACK_TIMEOUT_MILLIS: u64 = 2_000;
+const MAX_ACK_TIMEOUT_MILLIS: u64 = 30_000;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct AckQueryParams {
+    /// Wait until the clients acknowledged the command and return the result of every client.
+    #[serde(default)]
+    wait: bool,
+    /// Maximum time in milliseconds to wait for acknowledgements (default 2000, at most 30000).
+    timeout: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CommandResultDto {
+    clients: Vec<ClientCommandResultDto>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClientCommandResultDto {
+    client: usize,
+    status: ClientCommandStatusDto,
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ClientCommandStatusDto {
+    Applied,
+    Failed,
+    NoResponse,
+}
+
+impl From<ClientAck> for ClientCommandResultDto {
+    fn from(ack: ClientAck) -> Self {
+        let (status, error) = match ack.status {
+            AckStatus::Applied => (ClientCommandStatusDto::Applied, None),
+            AckStatus::Failed(error) => (ClientCommandStatusDto::Failed, Some(error)),
+            AckStatus::NoResponse => (ClientCommandStatusDto::NoResponse, None),
+        };
+        ClientCommandResultDto {
+            client: ack.client_id,
+            status,
+            error,
+        }
+    }
+}
+
+impl From<Vec<ClientAck>> for CommandResultDto {
+    fn from(acks: Vec<ClientAck>) -> Self {
+        CommandResultDto {
+            clients: acks.into_iter().map(ClientCommandResultDto::from).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct InstancesQueryParams {
+    /// Comma separated instance names and glob patterns like `lower-*`. Names are targeted even
+    /// if no client of the instance connected yet, patterns only match known instances.
+    instances: String,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MultiInstanceResultDto {
+    instances: Vec<InstanceDeliveryDto>,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct InstanceDeliveryDto {
+    instance: String,
+    /// Number of clients of the instance the command was sent to.
+    delivered: usize,
+    /// Result of every client, only if the caller waited for acknowledgements.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clients: Option<Vec<ClientCommandResultDto>>,
+}
+
+/// Clients a command was sent to and, if the caller waited, their acknowledgements.
+struct Delivery {
+    client_ids: Vec<usize>,
+    acks: Option<Vec<ClientAck>>,
+}
+
+/// Sends the message to the selected clients and responds with their acknowledgements if the
+/// caller asked to wait for them.
+pub(crate) async fn send_message_to_target(
+    instance: &str,
+    server: &WebsocketServer,
+    message: ServerMessage<'_>,
+    selector: Option<&ClientSelector>,
+    ack_params: &AckQueryParams,
+) -> Response {
+    let wait_timeout = get_ack_timeout(ack_params.wait, ack_params.timeout);
+    match send_message(instance, server, &message, selector, wait_timeout).await {
+        Some(result) => Json(result).into_response(),
+        None => StatusCode::OK.into_response(),
+    }
+}
+
+/// Returns how long to wait for acknowledgements, or nothing if the caller does not want to wait.
+pub(crate) fn get_ack_timeout(wait: bool, timeout_millis: Option<u64>) -> Option<Duration> {
+    wait.then(|| {
+        let timeout_millis = timeout_millis
+            .unwrap_or(DEFAULT_ACK_TIMEOUT_MILLIS)
+            .min(MAX_ACK_TIMEOUT_MILLIS);
+        Duration::from_millis(timeout_millis)
+    })
+}
+
+/// Sends the message to all clients of the instance or only to the selected clients. Returns the
+/// result of every client if a timeout to wait for their acknowledgements is given.
+pub(crate) async fn send_message(
+    instance: &str,
+    server: &WebsocketServer,
+    message: &ServerMessage<'_>,
+    selector: Option<&ClientSelector>,
+    wait_timeout: Option<Duration>,
+) -> Option<CommandResultDto> {
+    deliver(instance, server, message, selector, wait_timeout)
+        .await
+        .acks
+        .map(CommandResultDto::from)
+}
+
+/// Sends the message to the selected clients of every instance the `instances` parameter
+/// matches and responds with the number of clients per instance. The instances are served
+/// concurrently, so waiting for acknowledgements takes at most the timeout overall.
+pub(crate) async fn send_message_to_instances(
+    server: &WebsocketServer,
+    instances_params: &InstancesQueryParams,
+    message: ServerMessage<'_>,
+    selector: Option<&ClientSelector>,
+    ack_params: &AckQueryParams,
+) -> Response {
+    let instance_selector = match InstanceSelector::parse(&instances_params.instances) {
+        Ok(instance_selector) => instance_selector,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let wait_timeout = get_ack_timeout(ack_params.wait, ack_params.timeout);
+    let instances = server.resolve_instances(&instance_selector).await;
+    let deliveries = futures::future::join_all(
+        instances
+            .iter()
+            .map(|instance| deliver(instance, server, &message, selector, wait_timeout)),
+    )
+    .await;
+    let instances = instances
+        .into_iter()
+        .zip(deliveries)
+        .map(|(instance, delivery)| InstanceDeliveryDto {
+            instance,
+            delivered: delivery.client_ids.len(),
+            clients: delivery
+                .acks
+                .map(|acks| acks.into_iter().map(ClientCommandResultDto::from).collect()),
+        })
+        .collect();
+    Json(MultiInstanceResultDto { instances }).into_response()
+}
+
+async fn deliver(
+    instance: &str,
+    server: &WebsocketServer,
+    message: &ServerMessage<'_>,
+    selector: Option<&ClientSelector>,
+    wait_timeout: Option<Duration>,
+) -> Delivery {
+    if let Some(timeout) = wait_timeout {
+        let acks = server
+            .send_message_and_wait_for_acks(instance, selector, message, timeout)
+            .await;
+        return Delivery {
+            client_ids: acks.iter().map(|ack| ack.client_id).collect(),
+            acks: Some(acks),
+        };
+    }
+
+    let client_ids = if let Some(selector) = selector {
+        server
+            .send_message_to_selected_clients(instance, selector, message)
+            .await
+    } else {
+        server
+            .send_message_to_instance_clients(instance, message)
+            .await
+    };
+    Delivery {
+        client_ids,
+        acks: None,
+    }
+}
+
+/// Selects clients in query strings, for endpoints without a request body. Lists are comma
+/// separated, numeric entries of client lists are ids and all others names.
+#[derive(Deserialize, IntoParams, Default)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ClientSelectorQueryParams {
+    /// Only clients with one of these ids or names.
+    clients: Option<String>,
+    /// Only clients with at least one of these tags.
+    tags: Option<String>,
+    /// Leave out clients with these ids or names.
+    exclude_clients: Option<String>,
+    /// Leave out clients with any of these tags.
+    exclude_tags: Option<String>,
+}
+
+impl ClientSelectorQueryParams {
+    /// Returns no selector if no parameter is given, meaning all clients of the instance.
+    pub(crate) fn to_selector(&self) -> Result<Option<ClientSelector>, String> {
+        if self.clients.is_none()
+            && self.tags.is_none()
+            && self.exclude_clients.is_none()
+            && self.exclude_tags.is_none()
+        {
+            return Ok(None);
+        }
+        let (ids, names) = split_clients(self.clients.as_deref());
+        let (exclude_ids, exclude_names) = split_clients(self.exclude_clients.as_deref());
+        Ok(Some(ClientSelector::Group(ClientGroup {
+            ids,
+            names,
+            tags: parse_optional_tags(self.tags.as_deref())?,
+            exclude_ids,
+            exclude_names,
+            exclude_tags: parse_optional_tags(self.exclude_tags.as_deref())?,
+        })))
+    }
+}
+
+fn split_clients(clients: Option<&str>) -> (Vec<usize>, Vec<String>) {
+    let mut ids = Vec::new();
+    let mut names = Vec::new();
+    for client in clients
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|client| !client.is_empty())
+    {
+        match client.parse() {
+            Ok(id) => ids.push(id),
+            Err(_) => names.push(client.to_string()),
+        }
+    }
+    (ids, names)
+}
+
+fn parse_optional_tags(tags: Option<&str>) -> Result<Vec<String>, String> {
+    Ok(parse_tags(tags.unwrap_or_default())?.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector_from_query_params() {
+        assert_eq!(ClientSelectorQueryParams::default().to_selector(), Ok(None));
+        let params = ClientSelectorQueryParams {
+            clients: Some(String::from("3, program-left")),
+            tags: Some(String::from("left-wall")),
+            exclude_clients: None,
+            exclude_tags: Some(String::from("preview")),
+        };
+        assert_eq!(
+            params.to_selector(),
+            Ok(Some(ClientSelector::Group(ClientGroup {
+                ids: vec![3],
+                names: vec![String::from("program-left")],
+                tags: vec![String::from("left-wall")],
+                exclude_tags: vec![String::from("preview")],
+                ..ClientGroup::default()
+            })))
+        );
+        let invalid = ClientSelectorQueryParams {
+            tags: Some(String::from("left wall")),
+            ..ClientSelectorQueryParams::default()
+        };
+        assert!(invalid.to_selector().is_err());
+    }
+}
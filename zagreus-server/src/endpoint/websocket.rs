@@ -1,23 +1,58 @@
 use crate::ServerController;
+use crate::websocket::connection::ConnectionInfo;
+use crate::websocket::target::parse_tags;
 use axum::extract::ws::WebSocket;
-use axum::extract::{Extension, Path, WebSocketUpgrade};
-use axum::response::IntoResponse;
+use axum::extract::{ConnectInfo, Extension, Path, Query, WebSocketUpgrade};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+#[derive(Deserialize)]
+pub struct WebsocketQueryParams {
+    /// Name the template declares for the client, it stays the same across reconnects.
+    name: Option<String>,
+    /// Comma separated tags of the client, more tags can be assigned later.
+    tags: Option<String>,
+}
+
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     Path(instance): Path<String>,
+    Query(params): Query<WebsocketQueryParams>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Extension(server_controller): Extension<Arc<ServerController>>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|websocket| handle_socket(websocket, server_controller, instance))
+) -> Response {
+    let tags = match params.tags.as_deref().map(parse_tags).transpose() {
+        Ok(tags) => tags.unwrap_or_default(),
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let connection_info = ConnectionInfo {
+        remote_address,
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from),
+        name: params
+            .name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty()),
+        tags,
+    };
+    ws.on_upgrade(|websocket| {
+        handle_socket(websocket, server_controller, instance, connection_info)
+    })
+    .into_response()
 }
 
 pub async fn handle_socket(
     socket: WebSocket,
     server_controller: Arc<ServerController>,
     instance: String,
+    connection_info: ConnectionInfo,
 ) {
     server_controller
-        .add_websocket_client(socket, &instance)
+        .add_websocket_client(socket, &instance, connection_info)
         .await;
 }
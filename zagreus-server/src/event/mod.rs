@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+use crate::websocket::target::ClientSelector;
+
+pub mod rule;
+pub mod webhook;
+
+/// Number of events kept for subscribers that fall behind before they miss events.
+const EVENT_CAPACITY: usize = 1024;
+
+/// Something that happened on the server which control applications might want to know about.
+#[derive(Serialize, ToSchema, Clone, Debug, PartialEq)]
+#[serde(
+    tag = "type",
+    rename_all = "kebab-case",
+    rename_all_fields = "camelCase"
+)]
+pub enum ServerEvent {
+    ClientConnected {
+        instance: String,
+        client: usize,
+    },
+    ClientDisconnected {
+        instance: String,
+        client: usize,
+    },
+    StateSet {
+        instance: String,
+        client: usize,
+        name: String,
+        value: Option<String>,
+    },
+    LogError {
+        instance: String,
+        client: usize,
+        message: String,
+        stack: String,
+    },
+    AnimationStarted {
+        instance: String,
+        client: usize,
+        sequence: String,
+        queue_id: Option<String>,
+    },
+    AnimationFinished {
+        instance: String,
+        client: usize,
+        sequence: String,
+        queue_id: Option<String>,
+    },
+    AnimationCancelled {
+        instance: String,
+        client: usize,
+        sequence: String,
+        queue_id: Option<String>,
+    },
+    /// An event the template of a client sent, e.g. a button press.
+    CustomEvent {
+        instance: String,
+        client: usize,
+        name: String,
+        #[schema(value_type = Object)]
+        payload: serde_json::Value,
+    },
+    /// A command was sent to the clients of an instance, either to all of them or to one client.
+    Command {
+        instance: String,
+        client: Option<ClientSelector>,
+        delivered_to: Vec<usize>,
+        /// The message as it is sent over the websocket (tag and payload).
+        #[schema(value_type = Object)]
+        message: serde_json::Value,
+    },
+}
+
+impl ServerEvent {
+    /// Every value [`ServerEvent::get_type`] can return.
+    pub const TYPES: [&'static str; 9] = [
+        "client-connected",
+        "client-disconnected",
+        "state-set",
+        "log-error",
+        "animation-started",
+        "animation-finished",
+        "animation-cancelled",
+        "custom-event",
+        "command",
+    ];
+
+    pub fn get_type(&self) -> &'static str {
+        match self {
+            ServerEvent::ClientConnected { .. } => "client-connected",
+            ServerEvent::ClientDisconnected { .. } => "client-disconnected",
+            ServerEvent::StateSet { .. } => "state-set",
+            ServerEvent::LogError { .. } => "log-error",
+            ServerEvent::AnimationStarted { .. } => "animation-started",
+            ServerEvent::AnimationFinished { .. } => "animation-finished",
+            ServerEvent::AnimationCancelled { .. } => "animation-cancelled",
+            ServerEvent::CustomEvent { .. } => "custom-event",
+            ServerEvent::Command { .. } => "command",
+        }
+    }
+
+    pub fn get_instance(&self) -> &str {
+        match self {
+            ServerEvent::ClientConnected { instance, .. }
+            | ServerEvent::ClientDisconnected { instance, .. }
+            | ServerEvent::StateSet { instance, .. }
+            | ServerEvent::LogError { instance, .. }
+            | ServerEvent::AnimationStarted { instance, .. }
+            | ServerEvent::AnimationFinished { instance, .. }
+            | ServerEvent::AnimationCancelled { instance, .. }
+            | ServerEvent::CustomEvent { instance, .. }
+            | ServerEvent::Command { instance, .. } => instance,
+        }
+    }
+}
+
+/// Distributes server events to any number of subscribers. Publishing never waits for slow
+/// subscribers, they skip the events they could not keep up with instead.
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<ServerEvent>>,
+}
+
+impl Default for EventBus {
+    fn default() -> EventBus {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        EventBus { sender }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: ServerEvent) {
+        // without subscribers nobody is interested in the event
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    /// Lets publishers skip building events that are expensive to create when nobody listens.
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ServerEvent>> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_event() {
+        let event = ServerEvent::Command {
+            instance: String::from("main"),
+            client: None,
+            delivered_to: vec![1, 2],
+            message: serde_json::json!({"tag": "SetText", "payload": {"id": "a", "text": "b"}}),
+        };
+
+        let serialized = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(
+            serialized,
+            serde_json::json!({
+                "type": "command",
+                "instance": "main",
+                "client": null,
+                "deliveredTo": [1, 2],
+                "message": {"tag": "SetText", "payload": {"id": "a", "text": "b"}},
+            })
+        );
+        assert_eq!(serialized["type"], event.get_type());
+        assert!(ServerEvent::TYPES.contains(&event.get_type()));
+    }
+
+    #[tokio::test]
+    async fn test_publish_to_all_subscribers() {
+        let bus = EventBus::default();
+        assert!(!bus.has_subscribers());
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+        let event = ServerEvent::ClientConnected {
+            instance: String::from("main"),
+            client: 3,
+        };
+
+        assert!(bus.has_subscribers());
+        bus.publish(event.clone());
+
+        assert_eq!(*first.recv().await.unwrap(), event);
+        assert_eq!(*second.recv().await.unwrap(), event);
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::EventRuleConfig;
+use crate::data::asset::AssetStore;
+use crate::data::operation::resolve_aliases;
+use crate::event::ServerEvent;
+use crate::websocket::message::ServerMessage;
+use crate::websocket::server::WebsocketServer;
+use crate::websocket::target::InstanceSelector;
+
+/// Sends the operations of a rule to its target instances whenever a matching custom event
+/// arrives.
+pub struct EventRule {
+    config: EventRuleConfig,
+    instances: InstanceSelector,
+}
+
+impl EventRule {
+    pub fn new(config: EventRuleConfig) -> Result<EventRule, String> {
+        if config.operations.is_empty() {
+            return Err(String::from("At least one operation must be given."));
+        }
+        for (index, operation) in config.operations.iter().enumerate() {
+            operation
+                .validate()
+                .map_err(|message| format!("Operation {index} is invalid: {message}"))?;
+        }
+        let instances = InstanceSelector::parse(&config.instances)?;
+        Ok(EventRule { config, instances })
+    }
+
+    fn matches(&self, event: &ServerEvent) -> bool {
+        match event {
+            ServerEvent::CustomEvent { instance, name, .. } => {
+                *name == self.config.event
+                    && (self.config.source_instances.is_empty()
+                        || self.config.source_instances.contains(instance))
+            }
+            _ => false,
+        }
+    }
+
+    async fn apply(&self, server: &WebsocketServer, asset_store: &AssetStore) {
+        // aliases are resolved whenever the rule fires, so it shows what they point to now
+        let aliases = resolve_aliases(&self.config.operations, asset_store).await;
+        let message = ServerMessage::Batch {
+            messages: self
+                .config
+                .operations
+                .iter()
+                .map(|operation| operation.to_message(&aliases))
+                .collect(),
+        };
+        for instance in server.resolve_instances(&self.instances).await {
+            match &self.config.client {
+                Some(selector) => {
+                    server
+                        .send_message_to_selected_clients(&instance, selector, &message)
+                        .await
+                }
+                None => {
+                    server
+                        .send_message_to_instance_clients(&instance, &message)
+                        .await
+                }
+            };
+        }
+    }
+}
+
+async fn run(
+    rules: Vec<EventRule>,
+    server: Arc<WebsocketServer>,
+    asset_store: Arc<AssetStore>,
+    mut receiver: broadcast::Receiver<Arc<ServerEvent>>,
+) {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => {
+                for rule in rules.iter().filter(|rule| rule.matches(&event)) {
+                    debug!(
+                        "Applying rule for event {} from instance {}.",
+                        rule.config.event,
+                        event.get_instance()
+                    );
+                    rule.apply(&server, &asset_store).await;
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Event rules fell behind and skipped {} events.", skipped)
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Starts applying the rules. Fails without starting any rule if one of them is invalid.
+pub fn start_event_rules(
+    configs: &[EventRuleConfig],
+    server: Arc<WebsocketServer>,
+    asset_store: Arc<AssetStore>,
+) -> anyhow::Result<()> {
+    let rules = create_rules(configs)?;
+    if !rules.is_empty() {
+        info!("Applying {} event rules.", rules.len());
+        let receiver = server.subscribe_events();
+        tokio::spawn(run(rules, server, asset_store, receiver));
+    }
+    Ok(())
+}
+
+fn create_rules(configs: &[EventRuleConfig]) -> anyhow::Result<Vec<EventRule>> {
+    configs
+        .iter()
+        .map(|config| {
+            EventRule::new(config.clone())
+                .map_err(|err| anyhow!("Invalid rule for event {}: {}", config.event, err))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::operation::DataOperation;
+
+    fn rule_config(source_instances: Vec<String>, instances: &str) -> EventRuleConfig {
+        EventRuleConfig {
+            event: String::from("button-pressed"),
+            source_instances,
+            instances: String::from(instances),
+            client: None,
+            operations: vec![DataOperation::Animation {
+                name: String::from("in"),
+                queue: None,
+            }],
+        }
+    }
+
+    fn custom_event(instance: &str, name: &str) -> ServerEvent {
+        ServerEvent::CustomEvent {
+            instance: String::from(instance),
+            client: 0,
+            name: String::from(name),
+            payload: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn test_match_event() {
+        let rule = EventRule::new(rule_config(vec![String::from("tablet")], "program")).unwrap();
+        assert!(rule.matches(&custom_event("tablet", "button-pressed")));
+        assert!(!rule.matches(&custom_event("tablet", "other")));
+        assert!(!rule.matches(&custom_event("preview", "button-pressed")));
+        assert!(!rule.matches(&ServerEvent::ClientConnected {
+            instance: String::from("tablet"),
+            client: 0,
+        }));
+
+        let any_source = EventRule::new(rule_config(Vec::new(), "program")).unwrap();
+        assert!(any_source.matches(&custom_event("preview", "button-pressed")));
+    }
+
+    #[test]
+    fn test_fail_if_any_rule_is_invalid() {
+        let configs = [
+            rule_config(Vec::new(), "program"),
+            rule_config(Vec::new(), "program-["),
+        ];
+
+        let err = create_rules(&configs).err().unwrap();
+
+        assert!(err.to_string().contains("button-pressed"));
+        assert_eq!(1, create_rules(&configs[..1]).unwrap().len());
+    }
+
+    #[test]
+    fn test_reject_invalid_rule() {
+        assert!(EventRule::new(rule_config(Vec::new(), "program-[")).is_err());
+
+        let mut config = rule_config(Vec::new(), "program");
+        config.operations = vec![DataOperation::Text {
+            id: String::from(" "),
+            text: String::from("a"),
+        }];
+        assert!(EventRule::new(config).is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::config::WebhookConfig;
+use crate::event::ServerEvent;
+
+const EVENT_HEADER: &str = "X-Zagreus-Event";
+const DELIVERY_HEADER: &str = "X-Zagreus-Delivery";
+const SIGNATURE_HEADER: &str = "X-Zagreus-Signature";
+
+enum DeliveryError {
+    /// The receiver might accept the event later, e.g. after a restart.
+    Temporary(String),
+    /// The receiver rejected the event, sending it again would not change anything.
+    Permanent(String),
+}
+
+/// Posts matching server events to the URL of a webhook. Events are delivered one after another
+/// so that the receiver gets them in order, retries of one event delay all following events.
+pub struct Webhook {
+    config: WebhookConfig,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub fn new(config: WebhookConfig) -> anyhow::Result<Webhook> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_millis))
+            .build()?;
+        Ok(Webhook { config, client })
+    }
+
+    pub fn spawn(self, receiver: broadcast::Receiver<Arc<ServerEvent>>) -> JoinHandle<()> {
+        tokio::spawn(self.run(receiver))
+    }
+
+    async fn run(self, mut receiver: broadcast::Receiver<Arc<ServerEvent>>) {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if self.matches(&event) {
+                        self.deliver(&event).await;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => warn!(
+                    "Webhook {} fell behind and skipped {} events.",
+                    self.config.url, skipped
+                ),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    fn matches(&self, event: &ServerEvent) -> bool {
+        self.config
+            .events
+            .iter()
+            .any(|event_type| event_type == event.get_type())
+            && (self.config.instances.is_empty()
+                || self
+                    .config
+                    .instances
+                    .iter()
+                    .any(|instance| instance == event.get_instance()))
+    }
+
+    async fn deliver(&self, event: &ServerEvent) {
+        let body = match serde_json::to_vec(event) {
+            Ok(body) => body,
+            Err(err) => {
+                error!("Could not serialize event for webhook: {}.", err);
+                return;
+            }
+        };
+        // retries keep the delivery id so that receivers can detect duplicates
+        let delivery_id = new_delivery_id();
+
+        let mut retry_delay = Duration::from_millis(self.config.retry_delay_millis);
+        for attempt in 0..=self.config.max_retries {
+            match self.post(event.get_type(), &delivery_id, &body).await {
+                Ok(()) => return,
+                Err(DeliveryError::Permanent(reason)) => {
+                    error!(
+                        "Webhook {} rejected {} event: {}.",
+                        self.config.url,
+                        event.get_type(),
+                        reason
+                    );
+                    return;
+                }
+                Err(DeliveryError::Temporary(reason)) if attempt < self.config.max_retries => {
+                    warn!(
+                        "Could not deliver {} event to webhook {}, retrying in {} ms: {}.",
+                        event.get_type(),
+                        self.config.url,
+                        retry_delay.as_millis(),
+                        reason
+                    );
+                    tokio::time::sleep(retry_delay).await;
+                    retry_delay *= 2;
+                }
+                Err(DeliveryError::Temporary(reason)) => error!(
+                    "Giving up delivering {} event to webhook {}: {}.",
+                    event.get_type(),
+                    self.config.url,
+                    reason
+                ),
+            }
+        }
+    }
+
+    async fn post(
+        &self,
+        event_type: &str,
+        delivery_id: &str,
+        body: &[u8],
+    ) -> Result<(), DeliveryError> {
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event_type)
+            .header(DELIVERY_HEADER, delivery_id);
+        if let Some(secret) = &self.config.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, body));
+        }
+
+        let response = request
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|err| DeliveryError::Temporary(err.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else if status.is_server_error()
+            || status == reqwest::StatusCode::REQUEST_TIMEOUT
+            || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        {
+            Err(DeliveryError::Temporary(format!("status {status}")))
+        } else {
+            Err(DeliveryError::Permanent(format!("status {status}")))
+        }
+    }
+}
+
+/// Signature of the body in the form `sha256=<hex encoded HMAC-SHA256>`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={signature}")
+}
+
+/// Fails if a webhook lists an unknown event type, as it would never be delivered.
+/// Random, so that delivery ids stay unique across restarts of the server.
+fn new_delivery_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+pub fn start_webhooks(
+    configs: &[WebhookConfig],
+    subscribe: impl Fn() -> broadcast::Receiver<Arc<ServerEvent>>,
+) -> anyhow::Result<()> {
+    for config in configs {
+        validate_events(config)?;
+    }
+    for config in configs {
+        let url = config.url.clone();
+        match Webhook::new(config.clone()) {
+            Ok(webhook) => {
+                webhook.spawn(subscribe());
+                info!("Delivering events to webhook {}.", url);
+            }
+            Err(err) => error!("Could not set up webhook {}: {:#}.", url, err),
+        }
+    }
+    Ok(())
+}
+
+fn validate_events(config: &WebhookConfig) -> anyhow::Result<()> {
+    match config
+        .events
+        .iter()
+        .find(|event_type| !ServerEvent::TYPES.contains(&event_type.as_str()))
+    {
+        Some(event_type) => Err(anyhow!(
+            "Webhook {} lists unknown event type {}, known types are: {}.",
+            config.url,
+            event_type,
+            ServerEvent::TYPES.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventBus;
+    use axum::Router;
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct StandIn {
+        requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+    }
+
+    async fn receive(
+        State(stand_in): State<StandIn>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        let mut requests = stand_in.requests.lock().unwrap();
+        requests.push((headers, body));
+        // fail the first attempt to trigger a retry
+        if requests.len() == 1 {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    async fn start_stand_in() -> (String, StandIn) {
+        let stand_in = StandIn::default();
+        let router = Router::new()
+            .route("/hook", axum::routing::post(receive))
+            .with_state(stand_in.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        (url, stand_in)
+    }
+
+    #[test]
+    fn test_sign() {
+        // test case 2 of RFC 4231
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver_matching_events_with_retry() {
+        let (url, stand_in) = start_stand_in().await;
+        let bus = EventBus::default();
+        let webhook = Webhook::new(WebhookConfig {
+            url,
+            events: vec![String::from("log-error")],
+            instances: vec![String::from("main")],
+            secret: Some(String::from("secret")),
+            max_retries: 2,
+            retry_delay_millis: 10,
+            timeout_millis: 1000,
+        })
+        .unwrap();
+        let task = webhook.spawn(bus.subscribe());
+
+        bus.publish(ServerEvent::ClientConnected {
+            instance: String::from("main"),
+            client: 1,
+        });
+        bus.publish(ServerEvent::LogError {
+            instance: String::from("other"),
+            client: 2,
+            message: String::from("ignored"),
+            stack: String::new(),
+        });
+        bus.publish(ServerEvent::LogError {
+            instance: String::from("main"),
+            client: 1,
+            message: String::from("boom"),
+            stack: String::new(),
+        });
+
+        for _ in 0..100 {
+            if stand_in.requests.lock().unwrap().len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        task.abort();
+
+        let requests = stand_in.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        // the retry is delivered with the same id
+        let delivery_id = &requests[0].0[DELIVERY_HEADER];
+        assert_eq!(delivery_id.len(), 32);
+        for (headers, body) in requests.iter() {
+            let event: serde_json::Value = serde_json::from_slice(body).unwrap();
+            assert_eq!(event["message"], "boom");
+            assert_eq!(headers[EVENT_HEADER], "log-error");
+            assert_eq!(&headers[DELIVERY_HEADER], delivery_id);
+            assert_eq!(headers[SIGNATURE_HEADER], sign("secret", body).as_str());
+        }
+    }
+
+    #[test]
+    fn test_new_delivery_id() {
+        let delivery_id = new_delivery_id();
+
+        assert_eq!(delivery_id.len(), 32);
+        assert!(delivery_id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(delivery_id, new_delivery_id());
+    }
+
+    #[test]
+    fn test_reject_unknown_event_type() {
+        let bus = EventBus::default();
+        let config = WebhookConfig {
+            url: String::from("http://localhost/hook"),
+            events: vec![
+                String::from("client-connected"),
+                String::from("client-conected"),
+            ],
+            instances: Vec::new(),
+            secret: None,
+            max_retries: 0,
+            retry_delay_millis: 10,
+            timeout_millis: 1000,
+        };
+
+        let err = start_webhooks(&[config], || bus.subscribe()).unwrap_err();
+        assert!(err.to_string().contains("client-conected"));
+        assert!(!bus.has_subscribers());
+    }
+}
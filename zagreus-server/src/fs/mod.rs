@@ -1,11 +1,15 @@
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use anyhow::Context;
+use tokio::io::AsyncWriteExt;
 
 #[cfg(test)]
 pub mod temp;
 
 pub const ASSETS_SUBFOLDER_NAME: &str = "assets";
+const INSTANCES_SUBFOLDER_NAME: &str = "instances";
+const PRESETS_SUBFOLDER_NAME: &str = "presets";
 const ORGANIZATION_SUBFOLDER_NAME: &str = "zagreus";
 const LOGS_SUBFOLDER_NAME: &str = "logs";
 
@@ -32,6 +36,60 @@ pub fn get_assets_folder(data_folder_path: &Path) -> anyhow::Result<PathBuf> {
     Ok(folder)
 }
 
+pub fn get_instances_folder(data_folder_path: &Path) -> anyhow::Result<PathBuf> {
+    let folder = data_folder_path.join(INSTANCES_SUBFOLDER_NAME);
+    create_if_necessary(&folder)?;
+    Ok(folder)
+}
+
+pub fn get_presets_folder(data_folder_path: &Path) -> anyhow::Result<PathBuf> {
+    let folder = data_folder_path.join(PRESETS_SUBFOLDER_NAME);
+    create_if_necessary(&folder)?;
+    Ok(folder)
+}
+
+/// Counter making the names of temporary files unique within this process.
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Writes to a temporary file first and moves it to the target path afterwards, so a crash
+/// during the write never leaves a truncated file behind. Every write uses its own temporary
+/// file, so concurrent writes to the same path never move a partially written file into place.
+pub async fn write_file_atomically(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let mut temp_file_name = path
+        .file_name()
+        .with_context(|| format!("Path {path:?} does not point to a file"))?
+        .to_owned();
+    let counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    temp_file_name.push(format!(".{}.{}.tmp", std::process::id(), counter));
+    let temp_path = path.with_file_name(temp_file_name);
+
+    let result = write_and_move_file(&temp_path, path, contents).await;
+    if result.is_err()
+        && tokio::fs::try_exists(&temp_path).await.unwrap_or(false)
+        && let Err(err) = tokio::fs::remove_file(&temp_path).await
+    {
+        warn!("Could not remove temporary file {:?}: {}", temp_path, err);
+    }
+    result
+}
+
+async fn write_and_move_file(temp_path: &Path, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_path)
+        .await
+        .with_context(|| format!("Could not create file {temp_path:?}"))?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(temp_path, path)
+        .await
+        .with_context(|| format!("Could not move {temp_path:?} to {path:?}"))?;
+    Ok(())
+}
+
 fn create_if_necessary(path: &Path) -> anyhow::Result<()> {
     if !path.exists() {
         std::fs::create_dir_all(path)
@@ -66,4 +124,36 @@ mod tests {
         let path = get_profile_folder().unwrap();
         assert!(path.exists());
     }
+
+    #[tokio::test]
+    async fn test_write_file_atomically() {
+        let folder = temp::prepare_temp_folder().unwrap();
+        let path = folder.join("data.json");
+
+        write_file_atomically(&path, b"first").await.unwrap();
+        write_file_atomically(&path, b"second").await.unwrap();
+
+        assert_eq!("second", std::fs::read_to_string(&path).unwrap());
+        assert_eq!(1, std::fs::read_dir(&folder).unwrap().count());
+        temp::delete_temp_folder(&folder).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_file_atomically_concurrently() {
+        let folder = temp::prepare_temp_folder().unwrap();
+        let path = folder.join("data.json");
+        let contents: Vec<Vec<u8>> = (0..20).map(|index| vec![b'a' + index; 4096]).collect();
+
+        let writes = contents
+            .iter()
+            .map(|content| write_file_atomically(&path, content));
+        for result in futures::future::join_all(writes).await {
+            result.unwrap();
+        }
+
+        let written = std::fs::read(&path).unwrap();
+        assert!(contents.contains(&written));
+        assert_eq!(1, std::fs::read_dir(&folder).unwrap().count());
+        temp::delete_temp_folder(&folder).unwrap();
+    }
 }
@@ -14,6 +14,8 @@ use crate::cli::{ZagreusServerCommand, get_command};
 use crate::config::ZagreusServerConfig;
 use crate::config::loader::ConfigurationManager;
 use crate::controller::ServerController;
+use crate::data::asset::AssetStore;
+use crate::data::persistence::InstanceDataPersistence;
 use crate::websocket::server::WebsocketServer;
 
 mod cli;
@@ -21,6 +23,7 @@ mod config;
 mod controller;
 mod data;
 mod endpoint;
+mod event;
 mod fs;
 mod logger;
 mod websocket;
@@ -52,17 +55,37 @@ async fn start_with_config(configuration: ZagreusServerConfig) -> anyhow::Result
         "API docs are available at http://localhost:{}/static/swagger-docs/?url=/api/openapi.yaml",
         server_port
     );
-    let ws_server = Arc::new(WebsocketServer::new());
+    let persistence = if configuration.persist_instance_data {
+        Some(
+            InstanceDataPersistence::new(&configuration.data_folder)
+                .context("Could not prepare instance data persistence")?,
+        )
+    } else {
+        None
+    };
+    let ws_server = Arc::new(WebsocketServer::new(&configuration, persistence));
+    event::webhook::start_webhooks(&configuration.webhooks, || ws_server.subscribe_events())?;
+    let asset_store = Arc::new(
+        AssetStore::load(&configuration.data_folder).context("Could not load asset index")?,
+    );
+    event::rule::start_event_rules(
+        &configuration.event_rules,
+        ws_server.clone(),
+        asset_store.clone(),
+    )?;
 
     let server_controller = Arc::new(ServerController::new(ws_server.clone()));
 
     let router =
-        endpoint::routes::get_router(&configuration, ws_server.clone(), server_controller.clone())?;
+        endpoint::routes::get_router(&configuration, ws_server, server_controller, asset_store)?;
     let addr = SocketAddr::from(([0, 0, 0, 0], server_port));
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, router.into_make_service())
-        .await
-        .context("Could not run web server")?;
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .context("Could not run web server")?;
 
     Ok(())
 }
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+/// Result a client reported after applying a command. Contains the error message on failure.
+pub type CommandResult = Result<(), String>;
+
+pub enum AckStatus {
+    Applied,
+    Failed(String),
+    NoResponse,
+}
+
+pub struct ClientAck {
+    pub client_id: usize,
+    pub status: AckStatus,
+}
+
+/// Routes acknowledgements sent by the clients to the callers waiting for them. Only commands
+/// somebody waits for are registered, acknowledgements for all other commands are dropped.
+#[derive(Default)]
+pub struct PendingAcks {
+    pending: Mutex<HashMap<u64, UnboundedSender<(usize, CommandResult)>>>,
+}
+
+impl PendingAcks {
+    pub fn register(&self, command_id: u64) -> UnboundedReceiver<(usize, CommandResult)> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        self.lock().insert(command_id, sender);
+        receiver
+    }
+
+    pub fn unregister(&self, command_id: u64) {
+        self.lock().remove(&command_id);
+    }
+
+    pub fn resolve(&self, command_id: u64, client_id: usize, result: CommandResult) {
+        match self.lock().get(&command_id) {
+            Some(sender) => {
+                // the waiting caller may just have timed out, so a closed channel is fine
+                let _ = sender.send((client_id, result));
+            }
+            None => trace!(
+                "Nobody is waiting for the acknowledgement of command {}.",
+                command_id
+            ),
+        }
+    }
+
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<u64, UnboundedSender<(usize, CommandResult)>>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A command that was already sent and whose acknowledgements are collected later, so that the
+/// sender does not have to wait before sending the next command. The command is unregistered
+/// when it is dropped, also if nobody waited for its acknowledgements (e.g. because the request
+/// was cancelled).
+pub struct PendingCommand {
+    pub(super) command_id: u64,
+    pub(super) client_ids: Vec<usize>,
+    pub(super) receiver: UnboundedReceiver<(usize, CommandResult)>,
+    pub(super) deadline: tokio::time::Instant,
+    pub(super) pending_acks: Arc<PendingAcks>,
+}
+
+impl PendingCommand {
+    pub async fn wait_for_acks(mut self) -> Vec<ClientAck> {
+        collect_acks(&mut self.receiver, &self.client_ids, self.deadline).await
+    }
+}
+
+impl Drop for PendingCommand {
+    fn drop(&mut self) {
+        self.pending_acks.unregister(self.command_id);
+    }
+}
+
+/// Collects the acknowledgements of the given clients until all of them answered or the
+/// deadline is reached.
+pub async fn collect_acks(
+    receiver: &mut UnboundedReceiver<(usize, CommandResult)>,
+    client_ids: &[usize],
+    deadline: tokio::time::Instant,
+) -> Vec<ClientAck> {
+    let mut results: HashMap<usize, CommandResult> = HashMap::new();
+    while results.len() < client_ids.len() {
+        match tokio::time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Some((client_id, result))) => {
+                if client_ids.contains(&client_id) {
+                    results.insert(client_id, result);
+                }
+            }
+            Ok(None) | Err(_) => break,
+        }
+    }
+
+    client_ids
+        .iter()
+        .map(|client_id| ClientAck {
+            client_id: *client_id,
+            status: match results.remove(client_id) {
+                Some(Ok(())) => AckStatus::Applied,
+                Some(Err(error)) => AckStatus::Failed(error),
+                None => AckStatus::NoResponse,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_collect_acks() {
+        let pending_acks = PendingAcks::default();
+        let mut receiver = pending_acks.register(7);
+        pending_acks.resolve(7, 1, Ok(()));
+        pending_acks.resolve(7, 2, Err("No element with id Score".to_owned()));
+        pending_acks.resolve(8, 3, Ok(()));
+
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(50);
+        let acks = collect_acks(&mut receiver, &[1, 2, 3], deadline).await;
+        pending_acks.unregister(7);
+
+        assert!(matches!(acks[0].status, AckStatus::Applied));
+        assert!(matches!(&acks[1].status, AckStatus::Failed(error) if error.contains("Score")));
+        assert!(matches!(acks[2].status, AckStatus::NoResponse));
+    }
+
+    #[tokio::test]
+    async fn test_unregister_dropped_command() {
+        let pending_acks = Arc::new(PendingAcks::default());
+        let pending_command = PendingCommand {
+            command_id: 7,
+            client_ids: vec![1],
+            receiver: pending_acks.register(7),
+            deadline: tokio::time::Instant::now() + Duration::from_secs(10),
+            pending_acks: pending_acks.clone(),
+        };
+
+        // e.g. an HTTP request waiting for the acknowledgements is cancelled
+        let wait = tokio::time::timeout(Duration::from_millis(10), pending_command.wait_for_acks());
+        assert!(wait.await.is_err());
+
+        assert!(pending_acks.lock().is_empty());
+    }
+}
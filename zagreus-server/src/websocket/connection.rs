@@ -1,11 +1,37 @@
-use std::collections::HashMap;
-use tokio::sync::mpsc::UnboundedSender;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
 
-use crate::websocket::message::ServerMessage;
+use axum::extract::ws::{Message, Utf8Bytes};
+
+use crate::websocket::message::{ClientHello, PROTOCOL_VERSION, ServerCommand, ServerMessage};
+use crate::websocket::queue::{QueueStatistics, SendQueue};
+use crate::websocket::target::ClientIdentity;
 
 pub struct ClientState {
     pub client_id: usize,
     states: HashMap<String, String>,
+    animations: Vec<RunningAnimation>,
+}
+
+/// An animation sequence that a client reported as started and not yet finished.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunningAnimation {
+    pub sequence: String,
+    pub queue_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+impl RunningAnimation {
+    /// A queue runs one sequence at a time, a sequence without queue only runs once at a time.
+    fn runs_in_slot_of(&self, sequence: &str, queue_id: Option<&str>) -> bool {
+        match queue_id {
+            Some(queue_id) => self.queue_id.as_deref() == Some(queue_id),
+            None => self.queue_id.is_none() && self.sequence == sequence,
+        }
+    }
 }
 
 impl ClientState {
@@ -20,54 +46,344 @@ impl ClientState {
             self.states.remove(&state);
         }
     }
+
+    pub fn start_animation(&mut self, sequence: String, queue_id: Option<String>) {
+        self.animations
+            .retain(|animation| !animation.runs_in_slot_of(&sequence, queue_id.as_deref()));
+        self.animations.push(RunningAnimation {
+            sequence,
+            queue_id,
+            started_at: Utc::now(),
+        });
+    }
+
+    pub fn end_animation(&mut self, sequence: &str, queue_id: Option<&str>) {
+        self.animations.retain(|animation| {
+            animation.sequence != sequence || animation.queue_id.as_deref() != queue_id
+        });
+    }
+
+    pub fn get_running_animations(&self) -> &[RunningAnimation] {
+        &self.animations
+    }
+}
+
+/// Information about a client that is known when its websocket connection is established.
+pub struct ConnectionInfo {
+    pub remote_address: SocketAddr,
+    pub user_agent: Option<String>,
+    /// Name given in the websocket URL, takes precedence over the name in the hello.
+    pub name: Option<String>,
+    /// Tags given in the websocket URL.
+    pub tags: BTreeSet<String>,
+}
+
+/// A command that is serialized once and can then be queued for any number of clients.
+pub struct SerializedCommand {
+    tag: &'static str,
+    coalesce_key: Option<String>,
+    text: Utf8Bytes,
+}
+
+impl SerializedCommand {
+    pub fn new(command_id: u64, message: &ServerMessage) -> serde_json::Result<SerializedCommand> {
+        let command = ServerCommand {
+            id: command_id,
+            message,
+        };
+        Ok(SerializedCommand {
+            tag: message.get_tag(),
+            coalesce_key: message.get_coalesce_key(),
+            text: serde_json::to_string(&command)?.into(),
+        })
+    }
+}
+
+struct ConnectionActivity {
+    last_seen: DateTime<Utc>,
+    latency: Option<Duration>,
 }
 
+/// A connected client. Connections are shared between the server and the task reading from the
+/// websocket, so everything that changes while the client is connected is locked individually.
 pub struct WebsocketConnection {
-    message_sender: UnboundedSender<Result<axum::extract::ws::Message, axum::Error>>,
+    send_queue: Arc<SendQueue>,
     instance: String,
-    client_state: ClientState,
+    client_state: Mutex<ClientState>,
+    connection_info: ConnectionInfo,
+    hello: OnceLock<ClientHello>,
+    connected_at: DateTime<Utc>,
+    activity: Mutex<ConnectionActivity>,
+    tags: Mutex<BTreeSet<String>>,
+    /// Ids of the elements showing an image that was set to an alias, mapped to the alias.
+    image_aliases: Mutex<HashMap<String, String>>,
     pub client_id: usize,
 }
 
 impl WebsocketConnection {
     pub fn new(
         client_id: usize,
-        message_sender: UnboundedSender<Result<axum::extract::ws::Message, axum::Error>>,
+        send_queue: Arc<SendQueue>,
         instance: String,
+        connection_info: ConnectionInfo,
     ) -> WebsocketConnection {
         let client_state = ClientState {
             client_id,
             states: HashMap::new(),
+            animations: Vec::new(),
         };
+        let now = Utc::now();
+        let tags = connection_info.tags.clone();
         WebsocketConnection {
-            message_sender,
+            send_queue,
             instance,
-            client_state,
+            client_state: Mutex::new(client_state),
+            connection_info,
+            hello: OnceLock::new(),
+            connected_at: now,
+            activity: Mutex::new(ConnectionActivity {
+                last_seen: now,
+                latency: None,
+            }),
+            tags: Mutex::new(tags),
+            image_aliases: Mutex::new(HashMap::new()),
             client_id,
         }
     }
 
-    pub fn is_from_instance(&self, instance: &str) -> bool {
-        self.instance.eq(instance)
+    pub fn get_instance(&self) -> &str {
+        &self.instance
+    }
+
+    pub fn get_connection_info(&self) -> &ConnectionInfo {
+        &self.connection_info
+    }
+
+    /// Name the template declared for the client, it stays the same when the client reconnects.
+    pub fn get_name(&self) -> Option<&str> {
+        self.connection_info.name.as_deref().or_else(|| {
+            self.get_hello()
+                .and_then(|hello| hello.client_name.as_deref())
+        })
+    }
+
+    pub fn get_hello(&self) -> Option<&ClientHello> {
+        self.hello.get()
+    }
+
+    /// Returns false if the client already introduced itself, only the first hello counts.
+    pub fn set_hello(&self, hello: ClientHello) -> bool {
+        self.hello.set(hello).is_ok()
+    }
+
+    /// Whether the client uses a different runtime or protocol version than the server.
+    /// Clients that did not introduce themselves are not considered mismatched.
+    pub fn has_version_mismatch(&self) -> bool {
+        self.hello.get().is_some_and(|hello| {
+            hello.protocol_version != PROTOCOL_VERSION
+                || hello.runtime_version != crate::ZAGREUS_VERSION
+        })
     }
 
-    pub fn get_client_state(&self) -> &ClientState {
-        &self.client_state
+    /// Clients that did not introduce themselves are assumed to support every message.
+    pub fn supports_message(&self, message: &ServerMessage) -> bool {
+        self.hello
+            .get()
+            .is_none_or(|hello| message.is_supported_by(&hello.supported_messages))
     }
 
-    pub fn get_mut_client_state(&mut self) -> &mut ClientState {
-        &mut self.client_state
+    pub fn get_send_queue(&self) -> &Arc<SendQueue> {
+        &self.send_queue
     }
 
-    pub fn send_message(&self, message: &ServerMessage) {
-        match serde_json::to_string(message) {
-            Ok(serialized_message) => {
-                let ws_message = axum::extract::ws::Message::Text(serialized_message.into());
-                if let Err(err) = self.message_sender.send(Ok(ws_message)) {
-                    error!("Could not send websocket message on channel: {}.", err);
+    pub fn get_queue_statistics(&self) -> QueueStatistics {
+        self.send_queue.get_statistics()
+    }
+
+    pub fn get_connected_at(&self) -> DateTime<Utc> {
+        self.connected_at
+    }
+
+    pub fn get_last_seen(&self) -> DateTime<Utc> {
+        self.lock_activity().last_seen
+    }
+
+    /// Round-trip time of the last answered ping.
+    pub fn get_latency(&self) -> Option<Duration> {
+        self.lock_activity().latency
+    }
+
+    pub fn set_latency(&self, latency: Duration) {
+        self.lock_activity().latency = Some(latency);
+    }
+
+    /// Calls the function with the properties of the client that selectors look at.
+    pub fn with_identity<R>(&self, function: impl FnOnce(&ClientIdentity) -> R) -> R {
+        let tags = self.lock_tags();
+        function(&ClientIdentity {
+            id: self.client_id,
+            name: self.get_name(),
+            tags: &tags,
+        })
+    }
+
+    pub fn get_tags(&self) -> BTreeSet<String> {
+        self.lock_tags().clone()
+    }
+
+    pub fn set_tags(&self, tags: BTreeSet<String>) {
+        *self.lock_tags() = tags;
+    }
+
+    pub fn add_tags(&self, tags: impl IntoIterator<Item = String>) {
+        self.lock_tags().extend(tags);
+    }
+
+    fn lock_tags(&self) -> MutexGuard<'_, BTreeSet<String>> {
+        self.tags
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Records that a message was received from the client.
+    pub fn mark_seen(&self) {
+        self.lock_activity().last_seen = Utc::now();
+    }
+
+    pub fn get_client_state(&self) -> MutexGuard<'_, ClientState> {
+        self.client_state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Remembers which elements of the client show an image that was set to an alias, so that
+    /// they can be updated when the alias is pointed to another asset. Has to be called for
+    /// every message sent to the client.
+    pub fn track_image_aliases(&self, message: &ServerMessage) {
+        match message {
+            ServerMessage::SetImageSource { id, alias, .. } => {
+                let mut image_aliases = self.lock_image_aliases();
+                match alias {
+                    Some(alias) => {
+                        image_aliases.insert(id.to_string(), alias.to_string());
+                    }
+                    None => {
+                        image_aliases.remove(*id);
+                    }
+                }
+            }
+            ServerMessage::Batch { messages } => {
+                for message in messages {
+                    self.track_image_aliases(message);
                 }
             }
+            _ => {}
+        }
+    }
+
+    /// Ids of the elements showing an image that was set to the alias.
+    pub fn get_image_ids_with_alias(&self, alias: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .lock_image_aliases()
+            .iter()
+            .filter(|(_, image_alias)| *image_alias == alias)
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn lock_image_aliases(&self) -> MutexGuard<'_, HashMap<String, String>> {
+        self.image_aliases
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn send_message(&self, command_id: u64, message: &ServerMessage) {
+        self.track_image_aliases(message);
+        match SerializedCommand::new(command_id, message) {
+            Ok(command) => self.send_command(&command),
             Err(err) => error!("Could not serialize message: {}.", err),
         }
     }
+
+    /// Replays recorded data of the instance. It is queued regardless of the size of the send
+    /// queue, so that the client receives all of it.
+    pub fn replay_message(&self, command_id: u64, message: &ServerMessage) {
+        self.track_image_aliases(message);
+        match SerializedCommand::new(command_id, message) {
+            Ok(command) => self
+                .send_queue
+                .push_replayed(Message::Text(command.text), command.coalesce_key),
+            Err(err) => error!("Could not serialize message: {}.", err),
+        }
+    }
+
+    pub fn send_command(&self, command: &SerializedCommand) {
+        let ws_message = Message::Text(command.text.clone());
+        let result =
+            self.send_queue
+                .push(ws_message, Some(command.tag), command.coalesce_key.clone());
+        if result.is_err() {
+            warn!(
+                "Send queue of client with id {} is full, disconnecting it.",
+                self.client_id
+            );
+            self.send_queue.close();
+        }
+    }
+
+    fn lock_activity(&self) -> MutexGuard<'_, ConnectionActivity> {
+        self.activity
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_state() -> ClientState {
+        ClientState {
+            client_id: 0,
+            states: HashMap::new(),
+            animations: Vec::new(),
+        }
+    }
+
+    fn running_sequences(state: &ClientState) -> Vec<(&str, Option<&str>)> {
+        state
+            .get_running_animations()
+            .iter()
+            .map(|animation| (animation.sequence.as_str(), animation.queue_id.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_track_running_animations() {
+        let mut state = client_state();
+        state.start_animation(String::from("in"), Some(String::from("main")));
+        state.start_animation(String::from("pulse"), None);
+        state.start_animation(String::from("blink"), None);
+        assert_eq!(
+            running_sequences(&state),
+            vec![("in", Some("main")), ("pulse", None), ("blink", None)]
+        );
+
+        // the queue moved on to the next sequence
+        state.start_animation(String::from("out"), Some(String::from("main")));
+        // restarting a sequence without queue replaces it
+        state.start_animation(String::from("pulse"), None);
+        assert_eq!(
+            running_sequences(&state),
+            vec![("blink", None), ("out", Some("main")), ("pulse", None)]
+        );
+
+        state.end_animation("out", Some("main"));
+        state.end_animation("blink", None);
+        // finishing a sequence that was already replaced changes nothing
+        state.end_animation("in", Some("main"));
+        assert_eq!(running_sequences(&state), vec![("pulse", None)]);
+    }
 }
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use axum::body::Bytes;
+use tokio::time::Instant;
+
+use crate::config::HeartbeatConfig;
+
+/// Keeps track of the pings sent to a client and the pongs it answered with.
+pub struct Heartbeat {
+    interval: Duration,
+    timeout: Duration,
+    last_pong: Instant,
+    pending_ping: Option<(u64, Instant)>,
+    next_ping_id: u64,
+}
+
+impl Heartbeat {
+    /// Returns `None` if the heartbeat is disabled in the configuration.
+    pub fn new(config: &HeartbeatConfig, now: Instant) -> Option<Heartbeat> {
+        if config.interval_seconds == 0 {
+            return None;
+        }
+        Some(Heartbeat {
+            interval: Duration::from_secs(config.interval_seconds),
+            timeout: Duration::from_secs(config.timeout_seconds.max(config.interval_seconds)),
+            last_pong: now,
+            pending_ping: None,
+            next_ping_id: 0,
+        })
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn is_timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.last_pong) > self.timeout
+    }
+
+    /// Returns the payload of the next ping to send.
+    pub fn next_ping(&mut self, now: Instant) -> Bytes {
+        let ping_id = self.next_ping_id;
+        self.next_ping_id += 1;
+        self.pending_ping = Some((ping_id, now));
+        Bytes::copy_from_slice(&ping_id.to_be_bytes())
+    }
+
+    /// Returns the round-trip latency if the pong answers the last ping.
+    pub fn handle_pong(&mut self, payload: &[u8], now: Instant) -> Option<Duration> {
+        let (ping_id, sent_at) = self.pending_ping?;
+        if payload != ping_id.to_be_bytes() {
+            return None;
+        }
+        self.pending_ping = None;
+        self.last_pong = now;
+        Some(now.duration_since(sent_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(interval_seconds: u64, timeout_seconds: u64) -> HeartbeatConfig {
+        HeartbeatConfig {
+            interval_seconds,
+            timeout_seconds,
+        }
+    }
+
+    #[test]
+    fn test_disabled_heartbeat() {
+        assert!(Heartbeat::new(&config(0, 30), Instant::now()).is_none());
+    }
+
+    #[test]
+    fn test_pong_records_latency() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(&config(10, 30), start).unwrap();
+        let payload = heartbeat.next_ping(start);
+
+        assert_eq!(None, heartbeat.handle_pong(b"unrelated", start));
+        let latency = heartbeat.handle_pong(&payload, start + Duration::from_millis(15));
+        assert_eq!(Some(Duration::from_millis(15)), latency);
+    }
+
+    #[test]
+    fn test_missing_pong_times_out() {
+        let start = Instant::now();
+        let mut heartbeat = Heartbeat::new(&config(10, 30), start).unwrap();
+        let payload = heartbeat.next_ping(start + Duration::from_secs(10));
+
+        assert!(!heartbeat.is_timed_out(start + Duration::from_secs(30)));
+        assert!(heartbeat.is_timed_out(start + Duration::from_secs(31)));
+
+        heartbeat.handle_pong(&payload, start + Duration::from_secs(31));
+        assert!(!heartbeat.is_timed_out(start + Duration::from_secs(40)));
+    }
+}
@@ -1,9 +1,14 @@
 use std::borrow::Cow;
+use std::collections::HashSet;
 
 use crate::data::animation::config::AnimationSequence;
 use crate::data::asset::AssetSource;
 use crate::data::config::TemplateElement;
 
+/// Version of the message protocol between server and runtime. Has to be increased whenever
+/// messages change in an incompatible way.
+pub const PROTOCOL_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "tag", content = "payload")]
 pub enum ServerMessage<'a> {
@@ -43,6 +48,9 @@ pub enum ServerMessage<'a> {
         id: &'a str,
         asset: &'a str,
         asset_source: AssetSource,
+        /// Alias the asset was selected by, the runtime does not need it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alias: Option<&'a str>,
     },
     #[serde(rename_all = "camelCase")]
     SetCustomVariable {
@@ -53,6 +61,64 @@ pub enum ServerMessage<'a> {
         name: &'a str,
         value: Option<&'a str>,
     },
+    /// Several messages which the runtime applies in order within the same frame.
+    Batch {
+        #[serde(borrow)]
+        messages: Vec<ServerMessage<'a>>,
+    },
+}
+
+impl ServerMessage<'_> {
+    /// Name of the message as it appears in the `tag` field.
+    pub fn get_tag(&self) -> &'static str {
+        match self {
+            ServerMessage::SetText { .. } => "SetText",
+            ServerMessage::AddClass { .. } => "AddClass",
+            ServerMessage::RemoveClass { .. } => "RemoveClass",
+            ServerMessage::LoadAnimations { .. } => "LoadAnimations",
+            ServerMessage::LoadElements { .. } => "LoadElements",
+            ServerMessage::ExecuteAnimation { .. } => "ExecuteAnimation",
+            ServerMessage::OnLoad { .. } => "OnLoad",
+            ServerMessage::SetImageSource { .. } => "SetImageSource",
+            ServerMessage::SetCustomVariable { .. } => "SetCustomVariable",
+            ServerMessage::SetState { .. } => "SetState",
+            ServerMessage::Batch { .. } => "Batch",
+        }
+    }
+
+    /// Whether a runtime that declared the given messages can apply this message, including all
+    /// messages of a batch.
+    pub fn is_supported_by(&self, supported_messages: &HashSet<String>) -> bool {
+        supported_messages.contains(self.get_tag())
+            && match self {
+                ServerMessage::Batch { messages } => messages
+                    .iter()
+                    .all(|message| message.is_supported_by(supported_messages)),
+                _ => true,
+            }
+    }
+
+    /// Key of the element or variable a message sets the value of. A queued message can be
+    /// replaced by a newer message with the same key without changing the end result.
+    pub fn get_coalesce_key(&self) -> Option<String> {
+        match self {
+            ServerMessage::SetText { id, .. } => Some(format!("SetText:{id}")),
+            ServerMessage::SetImageSource { id, .. } => Some(format!("SetImageSource:{id}")),
+            ServerMessage::SetCustomVariable { name, .. } => {
+                Some(format!("SetCustomVariable:{name}"))
+            }
+            ServerMessage::SetState { name, .. } => Some(format!("SetState:{name}")),
+            _ => None,
+        }
+    }
+}
+
+/// A server message together with the command id the client refers to in its acknowledgement.
+#[derive(Serialize)]
+pub struct ServerCommand<'a> {
+    pub id: u64,
+    #[serde(flatten)]
+    pub message: &'a ServerMessage<'a>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -67,4 +133,129 @@ pub enum ClientMessage<'a> {
         message: String,
         stack: String,
     },
+    Ack {
+        id: u64,
+        ok: bool,
+        error: Option<String>,
+    },
+    Hello(ClientHello),
+    #[serde(rename_all = "camelCase")]
+    AnimationStarted {
+        sequence: String,
+        queue_id: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    AnimationFinished {
+        sequence: String,
+        queue_id: Option<String>,
+    },
+    /// The sequence was stopped before it finished or could not be started at all.
+    #[serde(rename_all = "camelCase")]
+    AnimationCancelled {
+        sequence: String,
+        queue_id: Option<String>,
+    },
+    /// An event the template defines itself, e.g. a button press on a tablet.
+    CustomEvent {
+        name: String,
+        #[serde(default)]
+        payload: serde_json::Value,
+    },
+}
+
+/// First message a client sends after connecting, describing the runtime it uses.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientHello {
+    pub runtime_version: String,
+    pub protocol_version: u32,
+    pub container: ContainerSize,
+    pub user_agent: String,
+    pub supported_messages: HashSet<String>,
+    /// Name the template declared for this client, see [`ClientSelector::Name`].
+    ///
+    /// [`ClientSelector::Name`]: crate::websocket::target::ClientSelector::Name
+    #[serde(default)]
+    pub client_name: Option<String>,
+    /// Tags the template declared for this client, they are added to the tags from the URL.
+    #[serde(default)]
+    pub client_tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ContainerSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_matches_serialized_tag() {
+        let message = ServerMessage::SetCustomVariable {
+            name: "--color",
+            value: "red",
+        };
+        let serialized: serde_json::Value = serde_json::to_value(&message).unwrap();
+        assert_eq!(message.get_tag(), serialized["tag"]);
+    }
+
+    #[test]
+    fn test_support_batch_only_with_all_messages() {
+        let supported_messages: HashSet<String> = ["Batch", "SetText"].map(String::from).into();
+        let set_text = || ServerMessage::SetText {
+            id: "title",
+            text: "Hello",
+        };
+        let set_state = || ServerMessage::SetState {
+            name: "scene",
+            value: None,
+        };
+        let batch = |messages| ServerMessage::Batch { messages };
+
+        assert!(batch(vec![set_text()]).is_supported_by(&supported_messages));
+        assert!(
+            !batch(vec![batch(vec![set_text(), set_state()])]).is_supported_by(&supported_messages)
+        );
+        assert!(!set_state().is_supported_by(&supported_messages));
+    }
+
+    #[test]
+    fn test_deserialize_hello() {
+        let message = r#"{"tag":"Hello","payload":{"runtimeVersion":"0.0.9","protocolVersion":1,"container":{"width":1920,"height":1080},"userAgent":"CEF","supportedMessages":["SetText"]}}"#;
+        match serde_json::from_str::<ClientMessage>(message).unwrap() {
+            ClientMessage::Hello(hello) => {
+                assert_eq!(1920, hello.container.width);
+                assert!(hello.supported_messages.contains("SetText"));
+            }
+            _ => panic!("Expected hello message"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_custom_event() {
+        let message = r#"{"tag":"CustomEvent","payload":{"name":"button-pressed"}}"#;
+        match serde_json::from_str::<ClientMessage>(message).unwrap() {
+            ClientMessage::CustomEvent { name, payload } => {
+                assert_eq!("button-pressed", name);
+                assert!(payload.is_null());
+            }
+            _ => panic!("Expected custom event message"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_animation_message() {
+        let message =
+            r#"{"tag":"AnimationFinished","payload":{"sequence":"out","queueId":"lower-third"}}"#;
+        match serde_json::from_str::<ClientMessage>(message).unwrap() {
+            ClientMessage::AnimationFinished { sequence, queue_id } => {
+                assert_eq!("out", sequence);
+                assert_eq!(Some("lower-third"), queue_id.as_deref());
+            }
+            _ => panic!("Expected animation finished message"),
+        }
+    }
 }
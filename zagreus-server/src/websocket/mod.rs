@@ -1,3 +1,8 @@
+pub mod ack;
 pub mod connection;
+pub mod heartbeat;
 pub mod message;
+pub mod queue;
 pub mod server;
+pub mod target;
+pub mod template_error;
@@ -0,0 +1,304 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::extract::ws::Message;
+use tokio::sync::Notify;
+
+use crate::config::{OverflowPolicy, SendQueueConfig};
+
+struct QueuedMessage {
+    message: Message,
+    coalesce_key: Option<String>,
+    replayed: bool,
+}
+
+#[derive(Default)]
+struct QueueState {
+    messages: VecDeque<QueuedMessage>,
+    /// Number of queued replayed messages, which do not count towards the size of the queue.
+    replayed: usize,
+    dropped: u64,
+    coalesced: u64,
+    closed: bool,
+}
+
+impl QueueState {
+    fn remove(&mut self, position: usize) -> QueuedMessage {
+        let queued = self
+            .messages
+            .remove(position)
+            .expect("position of a queued message");
+        if queued.replayed {
+            self.replayed -= 1;
+        }
+        queued
+    }
+
+    /// Replayed messages are kept, they do not take up space in the queue.
+    fn drop_oldest(&mut self) {
+        if let Some(position) = self.messages.iter().position(|queued| !queued.replayed) {
+            self.messages.remove(position);
+            self.dropped += 1;
+        }
+    }
+}
+
+pub struct QueueStatistics {
+    pub depth: usize,
+    pub capacity: usize,
+    pub dropped: u64,
+    pub coalesced: u64,
+}
+
+/// The queue overflowed for a message kind that is configured to disconnect the client.
+#[derive(Debug)]
+pub struct QueueOverflow;
+
+/// Bounded queue of messages waiting to be sent to a single client.
+pub struct SendQueue {
+    config: Arc<SendQueueConfig>,
+    state: Mutex<QueueState>,
+    available: Notify,
+    closed: Notify,
+}
+
+impl SendQueue {
+    pub fn new(config: Arc<SendQueueConfig>) -> SendQueue {
+        SendQueue {
+            config,
+            state: Mutex::new(QueueState::default()),
+            available: Notify::new(),
+            closed: Notify::new(),
+        }
+    }
+
+    /// Queues a message. Messages without kind (e.g. pings) are handled by the default policy.
+    /// Messages with the same coalesce key replace each other if the queue is full and the
+    /// policy allows it.
+    pub fn push(
+        &self,
+        message: Message,
+        kind: Option<&str>,
+        coalesce_key: Option<String>,
+    ) -> Result<(), QueueOverflow> {
+        let policy = kind
+            .and_then(|kind| self.config.policies.get(kind))
+            .copied()
+            .unwrap_or(self.config.default_policy);
+        let mut state = self.lock();
+        if state.closed {
+            return Ok(());
+        }
+
+        if state.messages.len() - state.replayed >= self.get_capacity() {
+            match policy {
+                OverflowPolicy::Disconnect => return Err(QueueOverflow),
+                OverflowPolicy::Coalesce => {
+                    let position = coalesce_key.as_ref().and_then(|key| {
+                        state
+                            .messages
+                            .iter()
+                            .position(|queued| queued.coalesce_key.as_ref() == Some(key))
+                    });
+                    match position {
+                        Some(position) => {
+                            state.remove(position);
+                            state.coalesced += 1;
+                        }
+                        None => state.drop_oldest(),
+                    }
+                }
+                OverflowPolicy::DropOldest => state.drop_oldest(),
+            }
+        }
+
+        state.messages.push_back(QueuedMessage {
+            message,
+            coalesce_key,
+            replayed: false,
+        });
+        drop(state);
+        self.available.notify_one();
+        Ok(())
+    }
+
+    /// Queues a message without applying the size of the queue and the overflow policies. Used
+    /// to replay the recorded data of an instance to a client, which would otherwise lose part
+    /// of it if the instance recorded more values than the queue holds.
+    pub fn push_replayed(&self, message: Message, coalesce_key: Option<String>) {
+        let mut state = self.lock();
+        if state.closed {
+            return;
+        }
+        state.messages.push_back(QueuedMessage {
+            message,
+            coalesce_key,
+            replayed: true,
+        });
+        state.replayed += 1;
+        drop(state);
+        self.available.notify_one();
+    }
+
+    /// Waits for the next message. Returns `None` once the queue is closed.
+    pub async fn pop(&self) -> Option<Message> {
+        loop {
+            {
+                let mut state = self.lock();
+                if state.closed {
+                    return None;
+                }
+                if !state.messages.is_empty() {
+                    return Some(state.remove(0).message);
+                }
+            }
+            self.available.notified().await;
+        }
+    }
+
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        state.messages.clear();
+        state.replayed = 0;
+        drop(state);
+        self.available.notify_one();
+        self.closed.notify_waiters();
+    }
+
+    /// Resolves once the queue was closed.
+    pub async fn wait_closed(&self) {
+        loop {
+            let notified = self.closed.notified();
+            if self.lock().closed {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    pub fn get_statistics(&self) -> QueueStatistics {
+        let state = self.lock();
+        QueueStatistics {
+            depth: state.messages.len(),
+            capacity: self.get_capacity(),
+            dropped: state.dropped,
+            coalesced: state.coalesced,
+        }
+    }
+
+    /// A queue always holds at least one message.
+    fn get_capacity(&self) -> usize {
+        self.config.size.max(1)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn queue(size: usize, policy: OverflowPolicy) -> SendQueue {
+        SendQueue::new(Arc::new(SendQueueConfig {
+            size,
+            default_policy: OverflowPolicy::DropOldest,
+            policies: HashMap::from([("SetText".to_owned(), policy)]),
+        }))
+    }
+
+    fn text(value: &str) -> Message {
+        Message::Text(value.to_owned().into())
+    }
+
+    async fn drain(queue: &SendQueue) -> Vec<String> {
+        let mut messages = Vec::new();
+        while queue.get_statistics().depth > 0 {
+            if let Some(Message::Text(text)) = queue.pop().await {
+                messages.push(text.to_string());
+            }
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let queue = queue(2, OverflowPolicy::DropOldest);
+        for value in ["1", "2", "3"] {
+            queue.push(text(value), Some("SetText"), None).unwrap();
+        }
+
+        assert_eq!(1, queue.get_statistics().dropped);
+        assert_eq!(vec!["2", "3"], drain(&queue).await);
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_same_key() {
+        let queue = queue(2, OverflowPolicy::Coalesce);
+        queue
+            .push(text("score 1"), Some("SetText"), Some("Score".to_owned()))
+            .unwrap();
+        queue
+            .push(text("clock 1"), Some("SetText"), Some("Clock".to_owned()))
+            .unwrap();
+        queue
+            .push(text("score 2"), Some("SetText"), Some("Score".to_owned()))
+            .unwrap();
+
+        let statistics = queue.get_statistics();
+        assert_eq!(1, statistics.coalesced);
+        assert_eq!(0, statistics.dropped);
+        assert_eq!(vec!["clock 1", "score 2"], drain(&queue).await);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_on_overflow() {
+        let queue = queue(1, OverflowPolicy::Disconnect);
+        queue.push(text("1"), Some("SetText"), None).unwrap();
+
+        assert!(queue.push(text("2"), Some("SetText"), None).is_err());
+        // other kinds fall back to the default policy
+        assert!(queue.push(text("3"), Some("AddClass"), None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_keep_replayed_messages() {
+        let queue = queue(2, OverflowPolicy::Disconnect);
+        for value in ["1", "2", "3"] {
+            queue.push_replayed(text(value), None);
+        }
+        queue.push(text("4"), Some("AddClass"), None).unwrap();
+        queue.push(text("5"), Some("SetText"), None).unwrap();
+        queue.push(text("6"), Some("AddClass"), None).unwrap();
+
+        let statistics = queue.get_statistics();
+        assert_eq!(1, statistics.dropped);
+        assert_eq!(5, statistics.depth);
+        assert_eq!(vec!["1", "2", "3", "5", "6"], drain(&queue).await);
+    }
+
+    #[test]
+    fn test_report_effective_capacity() {
+        assert_eq!(
+            1,
+            queue(0, OverflowPolicy::DropOldest)
+                .get_statistics()
+                .capacity
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_ends_pop() {
+        let queue = queue(1, OverflowPolicy::DropOldest);
+        queue.push(text("1"), None, None).unwrap();
+        queue.close();
+
+        assert!(queue.pop().await.is_none());
+        queue.wait_closed().await;
+    }
+}
@@ -1,36 +1,233 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
 
-use futures::FutureExt;
-use futures::StreamExt;
-use futures::stream::SplitStream;
-use tokio::sync::RwLock;
+use axum::extract::ws::Message;
+use chrono::Utc;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::{RwLock, broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
 
-use crate::websocket::connection::{ClientState, WebsocketConnection};
-use crate::websocket::message::{ClientMessage, ServerMessage};
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use crate::config::{HeartbeatConfig, SendQueueConfig, ZagreusServerConfig};
+use crate::data::asset::AssetSource;
+use crate::data::instance::InstanceData;
+use crate::data::persistence::InstanceDataPersistence;
+use crate::event::{EventBus, ServerEvent};
+use crate::websocket::ack::{ClientAck, PendingAcks, PendingCommand};
+use crate::websocket::connection::{
+    ClientState, ConnectionInfo, SerializedCommand, WebsocketConnection,
+};
+use crate::websocket::heartbeat::Heartbeat;
+use crate::websocket::message::{ClientHello, ClientMessage, PROTOCOL_VERSION, ServerMessage};
+use crate::websocket::queue::SendQueue;
+use crate::websocket::target::{ClientSelector, InstanceSelector, normalize_tags};
+use crate::websocket::template_error::{TemplateError, TemplateErrorLog};
 
-type UserConnections =
-    Arc<RwLock<HashMap<usize, crate::websocket::connection::WebsocketConnection>>>;
+type Instances = Arc<RwLock<HashMap<String, Arc<InstanceEntry>>>>;
+
+/// Connected clients, last known data and template errors of a single instance. They are locked
+/// per instance so that updates for one instance never wait for another one. Entries are created
+/// on first use and kept afterwards, lock order is always data first and connections second.
+struct InstanceEntry {
+    data: RwLock<Option<InstanceData>>,
+    connections: RwLock<HashMap<usize, Arc<WebsocketConnection>>>,
+    errors: std::sync::Mutex<TemplateErrorLog>,
+    /// Tags assigned through the API to named clients, they are re-applied when they reconnect.
+    assigned_tags: std::sync::Mutex<HashMap<String, BTreeSet<String>>>,
+    /// Marked as changed whenever a client reports a state or clients connect or disconnect.
+    client_states_changed: watch::Sender<()>,
+}
+
+impl InstanceEntry {
+    fn new(data: Option<InstanceData>, template_error_capacity: usize) -> InstanceEntry {
+        InstanceEntry {
+            data: RwLock::new(data),
+            connections: RwLock::default(),
+            errors: std::sync::Mutex::new(TemplateErrorLog::new(template_error_capacity)),
+            assigned_tags: std::sync::Mutex::default(),
+            client_states_changed: watch::Sender::new(()),
+        }
+    }
+
+    fn lock_errors(&self) -> std::sync::MutexGuard<'_, TemplateErrorLog> {
+        self.errors
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_assigned_tags(&self) -> std::sync::MutexGuard<'_, HashMap<String, BTreeSet<String>>> {
+        self.assigned_tags
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn notify_client_states_changed(&self) {
+        self.client_states_changed.send_replace(());
+    }
+
+    /// Replaces the tags of the connection with the ones assigned to its name, if there are any.
+    fn apply_assigned_tags(&self, connection: &WebsocketConnection) {
+        let Some(name) = connection.get_name() else {
+            return;
+        };
+        if let Some(tags) = self.lock_assigned_tags().get(name) {
+            connection.set_tags(tags.clone());
+        }
+    }
+}
+
+/// What the task of a connected client needs from the server.
+struct ClientContext {
+    entry: Arc<InstanceEntry>,
+    pending_acks: Arc<PendingAcks>,
+    events: Arc<EventBus>,
+    command_ids: Arc<AtomicU64>,
+}
 
 pub struct WebsocketServer {
     next_user_id: AtomicUsize,
-    connections: UserConnections,
+    next_command_id: Arc<AtomicU64>,
+    instances: Instances,
+    changed_instances_sender: Option<UnboundedSender<String>>,
+    pending_acks: Arc<PendingAcks>,
+    events: Arc<EventBus>,
+    heartbeat_config: HeartbeatConfig,
+    send_queue_config: Arc<SendQueueConfig>,
+    template_error_capacity: usize,
 }
 
 impl WebsocketServer {
-    pub fn new() -> WebsocketServer {
+    pub fn new(
+        configuration: &ZagreusServerConfig,
+        persistence: Option<InstanceDataPersistence>,
+    ) -> WebsocketServer {
+        let initial_instance_data = persistence
+            .as_ref()
+            .map(Self::restore_instance_data)
+            .unwrap_or_default();
+        let instances: Instances = Arc::new(RwLock::new(
+            initial_instance_data
+                .into_iter()
+                .map(|(instance, data)| {
+                    let entry =
+                        InstanceEntry::new(Some(data), configuration.template_error_capacity);
+                    (instance, Arc::new(entry))
+                })
+                .collect(),
+        ));
+
+        let changed_instances_sender = persistence.map(|persistence| {
+            let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+            tokio::spawn(Self::persist_changed_instances(
+                persistence,
+                instances.clone(),
+                receiver,
+            ));
+            sender
+        });
+
         WebsocketServer {
-            connections: Arc::new(RwLock::new(HashMap::new())),
             next_user_id: AtomicUsize::new(0),
+            next_command_id: Arc::new(AtomicU64::new(0)),
+            instances,
+            changed_instances_sender,
+            pending_acks: Arc::new(PendingAcks::default()),
+            events: Arc::new(EventBus::default()),
+            heartbeat_config: configuration.heartbeat.clone(),
+            send_queue_config: Arc::new(configuration.send_queue.clone()),
+            template_error_capacity: configuration.template_error_capacity,
         }
     }
 
+    fn restore_instance_data(
+        persistence: &InstanceDataPersistence,
+    ) -> HashMap<String, InstanceData> {
+        match persistence.load_all() {
+            Ok(instance_data) => {
+                info!("Restored data of {} instance(s).", instance_data.len());
+                instance_data
+            }
+            Err(err) => {
+                error!("Could not restore instance data: {:#}.", err);
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Writes the data of changed instances to disk. Changes that pile up while a write is in
+    /// progress are coalesced so that fast updates do not cause one write each.
+    async fn persist_changed_instances(
+        persistence: InstanceDataPersistence,
+        instances: Instances,
+        mut receiver: UnboundedReceiver<String>,
+    ) {
+        while let Some(instance) = receiver.recv().await {
+            let mut changed_instances = HashSet::from([instance]);
+            while let Ok(instance) = receiver.try_recv() {
+                changed_instances.insert(instance);
+            }
+
+            for instance in changed_instances {
+                let entry = instances.read().await.get(&instance).cloned();
+                let data = match entry {
+                    Some(entry) => entry.data.read().await.clone(),
+                    None => None,
+                };
+                let result = match data {
+                    Some(data) => persistence.store(&instance, &data).await,
+                    None => persistence.delete(&instance).await,
+                };
+                if let Err(err) = result {
+                    error!(
+                        "Could not persist data of instance {}: {:#}.",
+                        instance, err
+                    );
+                }
+            }
+        }
+    }
+
+    fn mark_instance_changed(&self, instance: &str) {
+        if let Some(sender) = &self.changed_instances_sender
+            && let Err(err) = sender.send(instance.to_string())
+        {
+            error!("Could not schedule persisting instance data: {}.", err);
+        }
+    }
+
+    /// Forgets the last known data of an instance, including its stored copy on disk.
+    pub async fn clear_instance_data(&self, instance: &str) {
+        if let Some(entry) = self.get_instance(instance).await {
+            *entry.data.write().await = None;
+            self.mark_instance_changed(instance);
+        }
+    }
+
+    async fn get_instance(&self, instance: &str) -> Option<Arc<InstanceEntry>> {
+        self.instances.read().await.get(instance).cloned()
+    }
+
+    async fn get_or_create_instance(&self, instance: &str) -> Arc<InstanceEntry> {
+        if let Some(entry) = self.get_instance(instance).await {
+            return entry;
+        }
+        self.instances
+            .write()
+            .await
+            .entry(instance.to_string())
+            .or_insert_with(|| Arc::new(InstanceEntry::new(None, self.template_error_capacity)))
+            .clone()
+    }
+
     pub async fn add_client_socket(
         &self,
         websocket: axum::extract::ws::WebSocket,
         template_name: &str,
+        connection_info: ConnectionInfo,
     ) {
         let id = self.next_user_id.fetch_add(1, Ordering::SeqCst);
         info!(
@@ -41,124 +238,692 @@ impl WebsocketServer {
         let (websocket_sink, websocket_stream) = websocket.split();
 
         // sending
-        let (sender_tx, sender_rx) = tokio::sync::mpsc::unbounded_channel();
-        let sender_rx = UnboundedReceiverStream::new(sender_rx);
-        let sending_stream =
-            tokio_stream::StreamExt::take_while(sender_rx, |result| match result {
-                Ok(_) => true,
-                Err(err) => {
-                    error!("Could not forward message to websocket sink: {}.", err);
-                    false
-                }
-            });
-        tokio::task::spawn(sending_stream.forward(websocket_sink).map(|result| {
-            if let Err(err) = result {
-                error!("Could not send message on websocket: {}.", err);
-            }
-        }));
+        let send_queue = Arc::new(SendQueue::new(self.send_queue_config.clone()));
+        let sending_task = tokio::task::spawn(Self::send_queued_messages(
+            send_queue.clone(),
+            websocket_sink,
+        ));
 
-        let connection = WebsocketConnection::new(id, sender_tx, String::from(template_name));
-        self.connections.write().await.insert(id, connection);
+        let connection = Arc::new(WebsocketConnection::new(
+            id,
+            send_queue.clone(),
+            String::from(template_name),
+            connection_info,
+        ));
 
-        // user messages and disconnect handler
+        // replay the last known data while holding the lock so no update can slip in between
+        let entry = self.get_or_create_instance(template_name).await;
+        entry.apply_assigned_tags(&connection);
+        let instance_data = entry.data.read().await;
+        if let Some(data) = instance_data.as_ref() {
+            for message in data.get_replay_messages() {
+                connection.replay_message(self.next_command_id(), &message);
+            }
+            Self::replay_targeted_states(&connection, data, &self.next_command_id);
+        }
+        entry
+            .connections
+            .write()
+            .await
+            .insert(id, connection.clone());
+        drop(instance_data);
+        entry.notify_client_states_changed();
+        self.events.publish(ServerEvent::ClientConnected {
+            instance: template_name.to_string(),
+            client: id,
+        });
+
+        // user messages, heartbeat and disconnect handler
+        let context = ClientContext {
+            entry,
+            pending_acks: self.pending_acks.clone(),
+            events: self.events.clone(),
+            command_ids: self.next_command_id.clone(),
+        };
         tokio::spawn(Self::handle_user_messages(
-            id,
+            connection,
             websocket_stream,
-            self.connections.clone(),
+            sending_task,
+            context,
+            Heartbeat::new(&self.heartbeat_config, Instant::now()),
         ));
     }
 
+    async fn send_queued_messages(
+        send_queue: Arc<SendQueue>,
+        mut sink: SplitSink<axum::extract::ws::WebSocket, Message>,
+    ) {
+        while let Some(message) = send_queue.pop().await {
+            if let Err(err) = sink.send(message).await {
+                error!("Could not send message on websocket: {}.", err);
+                send_queue.close();
+                return;
+            }
+        }
+        if let Err(err) = sink.close().await {
+            debug!("Could not close websocket: {}.", err);
+        }
+    }
+
     async fn handle_user_messages(
-        id: usize,
+        connection: Arc<WebsocketConnection>,
         mut stream: SplitStream<axum::extract::ws::WebSocket>,
-        connections: UserConnections,
+        sending_task: JoinHandle<()>,
+        context: ClientContext,
+        mut heartbeat: Option<Heartbeat>,
     ) {
+        let id = connection.client_id;
+        let send_queue = connection.get_send_queue().clone();
+        let mut heartbeat_interval = heartbeat.as_ref().map(|heartbeat| {
+            let mut interval = tokio::time::interval_at(
+                Instant::now() + heartbeat.get_interval(),
+                heartbeat.get_interval(),
+            );
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+
         loop {
-            match stream.next().await {
-                Some(message_result) => match message_result {
-                    Ok(message) => {
-                        match serde_json::from_slice::<ClientMessage>(&message.into_data()) {
-                            Ok(parsed_message) => match parsed_message {
-                                ClientMessage::LogError { message, stack } => {
-                                    error!("Template error occurred: {}\n{}", message, stack)
+            tokio::select! {
+                message_result = stream.next() => match message_result {
+                    Some(Ok(message)) => {
+                        connection.mark_seen();
+                        match message {
+                            Message::Text(_) | Message::Binary(_) => {
+                                Self::handle_client_message(
+                                    &connection,
+                                    &context,
+                                    &message.into_data(),
+                                )
+                                .await;
+                            }
+                            Message::Pong(payload) => {
+                                if let Some(latency) = heartbeat
+                                    .as_mut()
+                                    .and_then(|heartbeat| heartbeat.handle_pong(&payload, Instant::now()))
+                                {
+                                    connection.set_latency(latency);
                                 }
-                                ClientMessage::StateSet { name, value } => {
-                                    let mut locked_connections = connections.write().await;
-                                    if let Some(connection) = locked_connections.get_mut(&id) {
-                                        connection.get_mut_client_state().set_state(
-                                            name.to_string(),
-                                            value.map(|v| v.to_string()),
-                                        );
-                                    } else {
-                                        warn!("Did not find connection with id {id} anymore")
-                                    }
-                                }
-                            },
-                            Err(err) => error!("Could not parse message on websocket: {}.", err),
+                            }
+                            // pings are answered automatically
+                            Message::Ping(_) => {}
+                            Message::Close(_) => {
+                                debug!("Client with id {} closed the websocket.", id);
+                                break;
+                            }
                         }
                     }
-                    Err(err) => {
+                    Some(Err(err)) => {
                         error!("Could not receive message for client: {}.", err);
                         break;
                     }
+                    None => {
+                        warn!("Could not await new message on websocket.");
+                        break;
+                    }
                 },
-                None => {
-                    warn!("Could not await new message on websocket.");
+                _ = send_queue.wait_closed() => {
+                    debug!("Send queue of client with id {} was closed.", id);
                     break;
                 }
+                _ = Self::next_heartbeat_tick(&mut heartbeat_interval) => {
+                    if let Some(heartbeat) = heartbeat.as_mut() {
+                        let now = Instant::now();
+                        if heartbeat.is_timed_out(now) {
+                            warn!("Client with id {} did not answer pings anymore, disconnecting it.", id);
+                            break;
+                        }
+                        let ping = Message::Ping(heartbeat.next_ping(now));
+                        if send_queue.push(ping, None, None).is_err() {
+                            warn!("Send queue of client with id {} is full, disconnecting it.", id);
+                            break;
+                        }
+                    }
+                }
             }
         }
 
         // as soon as the loop quits the client has disconnected
-        Self::user_disconnected(&connections, id).await;
+        send_queue.close();
+        // the sending task might be stuck on a client that does not read anymore
+        sending_task.abort();
+        Self::user_disconnected(&context.entry, id).await;
+        context.events.publish(ServerEvent::ClientDisconnected {
+            instance: connection.get_instance().to_string(),
+            client: id,
+        });
+    }
+
+    async fn next_heartbeat_tick(interval: &mut Option<Interval>) {
+        match interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn handle_client_message(
+        connection: &WebsocketConnection,
+        context: &ClientContext,
+        data: &[u8],
+    ) {
+        let events = &context.events;
+        match serde_json::from_slice::<ClientMessage>(data) {
+            Ok(parsed_message) => match parsed_message {
+                ClientMessage::LogError { message, stack } => {
+                    error!("Template error occurred: {}\n{}", message, stack);
+                    context.entry.lock_errors().record(
+                        connection.client_id,
+                        message.clone(),
+                        stack.clone(),
+                        Utc::now(),
+                    );
+                    events.publish(ServerEvent::LogError {
+                        instance: connection.get_instance().to_string(),
+                        client: connection.client_id,
+                        message: message.to_string(),
+                        stack: stack.to_string(),
+                    });
+                }
+                ClientMessage::StateSet { name, value } => {
+                    let value = value.map(|v| v.to_string());
+                    connection
+                        .get_client_state()
+                        .set_state(name.to_string(), value.clone());
+                    context.entry.notify_client_states_changed();
+                    events.publish(ServerEvent::StateSet {
+                        instance: connection.get_instance().to_string(),
+                        client: connection.client_id,
+                        name: name.to_string(),
+                        value,
+                    });
+                }
+                ClientMessage::Ack {
+                    id: command_id,
+                    ok,
+                    error,
+                } => {
+                    let result = if ok {
+                        Ok(())
+                    } else {
+                        Err(error.unwrap_or_default())
+                    };
+                    context
+                        .pending_acks
+                        .resolve(command_id, connection.client_id, result);
+                }
+                ClientMessage::Hello(hello) => {
+                    Self::register_hello(connection, context, hello).await;
+                }
+                ClientMessage::AnimationStarted { sequence, queue_id } => {
+                    connection
+                        .get_client_state()
+                        .start_animation(sequence.clone(), queue_id.clone());
+                    events.publish(ServerEvent::AnimationStarted {
+                        instance: connection.get_instance().to_string(),
+                        client: connection.client_id,
+                        sequence,
+                        queue_id,
+                    });
+                }
+                ClientMessage::AnimationFinished { sequence, queue_id } => {
+                    connection
+                        .get_client_state()
+                        .end_animation(&sequence, queue_id.as_deref());
+                    events.publish(ServerEvent::AnimationFinished {
+                        instance: connection.get_instance().to_string(),
+                        client: connection.client_id,
+                        sequence,
+                        queue_id,
+                    });
+                }
+                ClientMessage::AnimationCancelled { sequence, queue_id } => {
+                    connection
+                        .get_client_state()
+                        .end_animation(&sequence, queue_id.as_deref());
+                    events.publish(ServerEvent::AnimationCancelled {
+                        instance: connection.get_instance().to_string(),
+                        client: connection.client_id,
+                        sequence,
+                        queue_id,
+                    });
+                }
+                ClientMessage::CustomEvent { name, payload } => {
+                    debug!(
+                        "Client {} of instance {} sent event {}.",
+                        connection.client_id,
+                        connection.get_instance(),
+                        name
+                    );
+                    events.publish(ServerEvent::CustomEvent {
+                        instance: connection.get_instance().to_string(),
+                        client: connection.client_id,
+                        name,
+                        payload,
+                    });
+                }
+            },
+            Err(err) => error!("Could not parse message on websocket: {}.", err),
+        }
     }
 
-    async fn user_disconnected(connections: &UserConnections, id: usize) {
+    async fn register_hello(
+        connection: &WebsocketConnection,
+        context: &ClientContext,
+        mut hello: ClientHello,
+    ) {
+        let id = connection.client_id;
+        info!(
+            "Client with id {} uses runtime version {} with protocol version {}.",
+            id, hello.runtime_version, hello.protocol_version
+        );
+        hello.client_name = hello
+            .client_name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        if let (Some(url_name), Some(hello_name)) =
+            (&connection.get_connection_info().name, &hello.client_name)
+            && url_name != hello_name
+        {
+            warn!(
+                "Client with id {} declared name {} in its hello but {} in the websocket URL, using {}.",
+                id, hello_name, url_name, url_name
+            );
+        }
+        let tags = match normalize_tags(std::mem::take(&mut hello.client_tags)) {
+            Ok(tags) => tags,
+            Err(message) => {
+                warn!("Client with id {} declared invalid tags: {}", id, message);
+                BTreeSet::new()
+            }
+        };
+        if !connection.set_hello(hello) {
+            warn!(
+                "Client with id {} sent more than one hello, ignoring it.",
+                id
+            );
+            return;
+        }
+        connection.add_tags(tags);
+        // tags assigned through the API take precedence over the ones the client declared
+        context.entry.apply_assigned_tags(connection);
+        // the name and tags of the hello can select the client for more states
+        if let Some(data) = context.entry.data.read().await.as_ref() {
+            Self::replay_targeted_states(connection, data, &context.command_ids);
+        }
+        if connection.has_version_mismatch() {
+            warn!(
+                "Client with id {} of instance {} uses a runtime that does not match server version {} (protocol version {}). Please reload the template with the current zagreus-runtime.js.",
+                id,
+                connection.get_instance(),
+                crate::ZAGREUS_VERSION,
+                PROTOCOL_VERSION
+            );
+        }
+    }
+
+    fn replay_targeted_states(
+        connection: &WebsocketConnection,
+        data: &InstanceData,
+        command_ids: &AtomicU64,
+    ) {
+        let messages = connection.with_identity(|client| data.get_targeted_replay_messages(client));
+        for message in messages {
+            connection.replay_message(command_ids.fetch_add(1, Ordering::SeqCst), &message);
+        }
+    }
+
+    async fn user_disconnected(entry: &InstanceEntry, id: usize) {
         debug!("Client with id {} has disconnected.", id);
-        connections.write().await.remove(&id);
+        entry.connections.write().await.remove(&id);
+        entry.notify_client_states_changed();
+    }
+
+    fn next_command_id(&self) -> u64 {
+        self.next_command_id.fetch_add(1, Ordering::SeqCst)
     }
 
+    /// Returns the ids of the clients the message was sent to.
     pub async fn send_message_to_instance_clients(
         &self,
         instance: &str,
         message: &ServerMessage<'_>,
-    ) {
-        let locked_connections = self.connections.read().await;
-        let connection_entries = locked_connections.values();
+    ) -> Vec<usize> {
+        self.send_command(instance, None, self.next_command_id(), message)
+            .await
+    }
 
-        for connection in connection_entries {
-            if connection.is_from_instance(instance) {
-                connection.send_message(message);
-            }
+    /// Returns the ids of the clients the message was sent to.
+    pub async fn send_message_to_selected_clients(
+        &self,
+        instance: &str,
+        selector: &ClientSelector,
+        message: &ServerMessage<'_>,
+    ) -> Vec<usize> {
+        self.send_command(instance, Some(selector), self.next_command_id(), message)
+            .await
+    }
+
+    /// Sends the message to all clients of the instance (or only the selected clients) and waits
+    /// until every client that received it acknowledged it or the timeout elapsed.
+    pub async fn send_message_and_wait_for_acks(
+        &self,
+        instance: &str,
+        selector: Option<&ClientSelector>,
+        message: &ServerMessage<'_>,
+        timeout: Duration,
+    ) -> Vec<ClientAck> {
+        self.send_message_with_acks(instance, selector, message, timeout)
+            .await
+            .wait_for_acks()
+            .await
+    }
+
+    /// Sends the message like [`WebsocketServer::send_message_and_wait_for_acks`], but returns as
+    /// soon as it was sent. The acknowledgements can be awaited with the returned command.
+    pub async fn send_message_with_acks(
+        &self,
+        instance: &str,
+        selector: Option<&ClientSelector>,
+        message: &ServerMessage<'_>,
+        timeout: Duration,
+    ) -> PendingCommand {
+        let command_id = self.next_command_id();
+        let deadline = tokio::time::Instant::now() + timeout;
+        // register before sending so that no acknowledgement can arrive too early
+        let receiver = self.pending_acks.register(command_id);
+        let client_ids = self
+            .send_command(instance, selector, command_id, message)
+            .await;
+        PendingCommand {
+            command_id,
+            client_ids,
+            receiver,
+            deadline,
+            pending_acks: self.pending_acks.clone(),
         }
     }
 
-    pub async fn send_message_to_instance_client(
+    async fn send_command(
         &self,
         instance: &str,
-        client_id: usize,
+        selector: Option<&ClientSelector>,
+        command_id: u64,
         message: &ServerMessage<'_>,
-    ) {
-        let locked_connections = self.connections.read().await;
-        let connection_entries = locked_connections.values();
+    ) -> Vec<usize> {
+        // messages for selected clients do not change the data replayed to the whole instance,
+        // only their states are kept if the selector still matches after a reconnect
+        let records_message = match selector {
+            None => InstanceData::is_recorded_message(message),
+            Some(selector) => {
+                selector.is_stable() && InstanceData::is_recorded_targeted_message(message)
+            }
+        };
+        let entry = if records_message {
+            self.get_or_create_instance(instance).await
+        } else {
+            match self.get_instance(instance).await {
+                Some(entry) => entry,
+                None => return Vec::new(),
+            }
+        };
+
+        let _instance_data = if selector.is_none() || records_message {
+            let mut instance_data = entry.data.write().await;
+            if records_message {
+                let data = instance_data.get_or_insert_default();
+                match selector {
+                    None => data.apply_message(message),
+                    Some(selector) => data.apply_targeted_message(selector, message),
+                }
+                self.mark_instance_changed(instance);
+            }
+            Some(instance_data)
+        } else {
+            None
+        };
+
+        let command = match SerializedCommand::new(command_id, message) {
+            Ok(command) => command,
+            Err(err) => {
+                error!("Could not serialize message: {}.", err);
+                return Vec::new();
+            }
+        };
+
+        let connections = entry.connections.read().await;
+        let targets: Vec<&Arc<WebsocketConnection>> = match selector {
+            Some(selector) => match selector.get_single_id() {
+                Some(client_id) => connections.get(&client_id).into_iter().collect(),
+                None => connections
+                    .values()
+                    .filter(|connection| selector.matches(connection))
+                    .collect(),
+            },
+            None => connections.values().collect(),
+        };
+        let mut client_ids = Vec::new();
+        for connection in targets {
+            if !connection.supports_message(message) {
+                debug!(
+                    "Not sending message {} to client {} as it does not support it.",
+                    message.get_tag(),
+                    connection.client_id
+                );
+                continue;
+            }
+            connection.track_image_aliases(message);
+            connection.send_command(&command);
+            client_ids.push(connection.client_id);
+        }
+        drop(connections);
+
+        // the message is only serialized a second time if someone listens to the events
+        if self.events.has_subscribers() {
+            match serde_json::to_value(message) {
+                Ok(message) => self.events.publish(ServerEvent::Command {
+                    instance: instance.to_string(),
+                    client: selector.cloned(),
+                    delivered_to: client_ids.clone(),
+                    message,
+                }),
+                Err(err) => error!("Could not serialize message for event: {}.", err),
+            }
+        }
+        client_ids
+    }
+
+    /// Replaces the tags of the selected clients and returns their ids. Tags of named clients are
+    /// remembered and assigned again when they reconnect. When selecting a single client by name,
+    /// the tags are remembered even if the client is not connected yet.
+    pub async fn set_client_tags(
+        &self,
+        instance: &str,
+        selector: &ClientSelector,
+        tags: BTreeSet<String>,
+    ) -> Vec<usize> {
+        let entry = match selector {
+            ClientSelector::Name(name) => {
+                let entry = self.get_or_create_instance(instance).await;
+                entry
+                    .lock_assigned_tags()
+                    .insert(name.clone(), tags.clone());
+                entry
+            }
+            _ => match self.get_instance(instance).await {
+                Some(entry) => entry,
+                None => return Vec::new(),
+            },
+        };
+
+        let connections = entry.connections.read().await;
+        let mut client_ids = Vec::new();
+        for connection in connections
+            .values()
+            .filter(|connection| selector.matches(connection))
+        {
+            if let Some(name) = connection.get_name() {
+                entry
+                    .lock_assigned_tags()
+                    .insert(name.to_string(), tags.clone());
+            }
+            connection.set_tags(tags.clone());
+            client_ids.push(connection.client_id);
+        }
+        client_ids
+    }
+
+    /// Returns the template errors of the instance, the most recently reported error last.
+    pub async fn get_template_errors(&self, instance: &str) -> Vec<TemplateError> {
+        match self.get_instance(instance).await {
+            Some(entry) => entry.lock_errors().get_errors().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Forgets the template errors of one client or of all clients of the instance.
+    pub async fn clear_template_errors(&self, instance: &str, client: Option<usize>) {
+        if let Some(entry) = self.get_instance(instance).await {
+            entry.lock_errors().clear(client);
+        }
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<Arc<ServerEvent>> {
+        self.events.subscribe()
+    }
+
+    /// Sets the new asset of the alias on every element that shows the alias, also on elements
+    /// that were set for selected clients only, and returns the instances whose clients were
+    /// updated.
+    pub async fn update_image_alias(&self, alias: &str, asset: &str) -> Vec<String> {
+        let entries: Vec<(String, Arc<InstanceEntry>)> = self
+            .instances
+            .read()
+            .await
+            .iter()
+            .map(|(instance, entry)| (instance.clone(), entry.clone()))
+            .collect();
+        let mut updated_instances = Vec::new();
+        for (instance, entry) in entries {
+            // the data replayed to clients that connect later
+            if let Some(data) = entry.data.write().await.as_mut()
+                && data.repoint_image_alias(alias, asset)
+            {
+                self.mark_instance_changed(&instance);
+            }
+
+            let mut updated = false;
+            for connection in entry.connections.read().await.values() {
+                let ids = connection.get_image_ids_with_alias(alias);
+                if ids.is_empty() {
+                    continue;
+                }
+                let message = ServerMessage::Batch {
+                    messages: ids
+                        .iter()
+                        .map(|id| ServerMessage::SetImageSource {
+                            id,
+                            asset,
+                            asset_source: AssetSource::Zagreus,
+                            alias: Some(alias),
+                        })
+                        .collect(),
+                };
+                if connection.supports_message(&message) {
+                    connection.send_message(self.next_command_id(), &message);
+                    updated = true;
+                }
+            }
+            if updated {
+                updated_instances.push(instance);
+            }
+        }
+        updated_instances.sort();
+        updated_instances
+    }
 
-        for connection in connection_entries {
-            if connection.client_id == client_id && connection.is_from_instance(instance) {
-                connection.send_message(message);
+    /// Returns the names of all instances with at least one connected client together with the
+    /// number of connected clients.
+    pub async fn get_instance_client_counts(&self) -> HashMap<String, usize> {
+        // do not hold the lock on all instances while looking at each of them
+        let entries: Vec<(String, Arc<InstanceEntry>)> = self
+            .instances
+            .read()
+            .await
+            .iter()
+            .map(|(instance, entry)| (instance.clone(), entry.clone()))
+            .collect();
+        let mut client_counts = HashMap::new();
+        for (instance, entry) in entries {
+            let client_count = entry.connections.read().await.len();
+            if client_count > 0 {
+                client_counts.insert(instance, client_count);
             }
         }
+        client_counts
+    }
+
+    /// Returns the instances the selector matches. Patterns are matched against every instance
+    /// that had clients since the server started or has stored data.
+    pub async fn resolve_instances(&self, selector: &InstanceSelector) -> Vec<String> {
+        let instances = self.instances.read().await;
+        selector.resolve(instances.keys().map(String::as_str))
     }
 
-    pub async fn iterate_client_states<F>(&self, instance: &str, consumer: F)
+    /// Calls the consumer with every connection of the instance and the value the server wants
+    /// it to have for the given state, see [`InstanceData::get_desired_state`].
+    pub async fn iterate_desired_state<F>(&self, instance: &str, name: &str, mut consumer: F)
     where
-        F: FnMut(&ClientState),
+        F: FnMut(&WebsocketConnection, Option<Option<&str>>),
+    {
+        if let Some(entry) = self.get_instance(instance).await {
+            let instance_data = entry.data.read().await;
+            for connection in entry.connections.read().await.values() {
+                let desired = instance_data.as_ref().and_then(|data| {
+                    connection.with_identity(|client| data.get_desired_state(name, client))
+                });
+                consumer(connection, desired);
+            }
+        }
+    }
+
+    /// Returns a receiver that is notified whenever a client of the instance reports a state
+    /// or clients connect or disconnect. Subscribe before looking at the client states to not
+    /// miss a change in between.
+    pub async fn subscribe_client_state_changes(
+        &self,
+        instance: &str,
+    ) -> Option<watch::Receiver<()>> {
+        // waiting must not create instances, they would never be removed again
+        self.get_instance(instance)
+            .await
+            .map(|entry| entry.client_states_changed.subscribe())
+    }
+
+    pub async fn iterate_connections<F>(&self, instance: &str, consumer: F)
+    where
+        F: FnMut(&WebsocketConnection),
     {
-        let locked_connections = self.connections.read().await;
-        let connection_entries = locked_connections.values();
+        if let Some(entry) = self.get_instance(instance).await {
+            entry
+                .connections
+                .read()
+                .await
+                .values()
+                .map(|connection| connection.as_ref())
+                .for_each(consumer);
+        }
+    }
 
-        connection_entries
-            .filter(|connection| connection.is_from_instance(instance))
-            .map(|connection| connection.get_client_state())
-            .for_each(consumer);
+    pub async fn iterate_client_states<F>(&self, instance: &str, mut consumer: F)
+    where
+        F: FnMut(&ClientState),
+    {
+        if let Some(entry) = self.get_instance(instance).await {
+            for connection in entry.connections.read().await.values() {
+                consumer(&connection.get_client_state());
+            }
+        }
     }
 }
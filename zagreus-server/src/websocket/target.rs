@@ -0,0 +1,301 @@
+use std::collections::BTreeSet;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use utoipa::ToSchema;
+
+use crate::websocket::connection::WebsocketConnection;
+
+/// Selects clients of an instance: a single client by its id which changes on every reconnect,
+/// by the name the template declared for itself or a group of clients.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ClientSelector {
+    Id(usize),
+    Name(String),
+    Group(ClientGroup),
+}
+
+/// Clients that match any of the ids, names or tags and none of the exclusions. Without ids,
+/// names and tags, all clients that are not excluded are selected.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ClientGroup {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub names: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_ids: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_names: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_tags: Vec<String>,
+}
+
+/// The properties of a client that selectors look at.
+pub struct ClientIdentity<'a> {
+    pub id: usize,
+    pub name: Option<&'a str>,
+    pub tags: &'a BTreeSet<String>,
+}
+
+impl ClientSelector {
+    pub fn matches(&self, connection: &WebsocketConnection) -> bool {
+        connection.with_identity(|client| self.matches_identity(client))
+    }
+
+    pub fn matches_identity(&self, client: &ClientIdentity) -> bool {
+        match self {
+            ClientSelector::Id(client_id) => client.id == *client_id,
+            ClientSelector::Name(name) => client.name == Some(name.as_str()),
+            ClientSelector::Group(group) => group.matches(client),
+        }
+    }
+
+    /// Whether the selector still selects the same clients after they reconnected, which is not
+    /// the case if it refers to client ids.
+    pub fn is_stable(&self) -> bool {
+        match self {
+            ClientSelector::Id(_) => false,
+            ClientSelector::Name(_) => true,
+            ClientSelector::Group(group) => group.ids.is_empty() && group.exclude_ids.is_empty(),
+        }
+    }
+
+    /// The id of the only client that can match, if the selector is that specific.
+    pub fn get_single_id(&self) -> Option<usize> {
+        match self {
+            ClientSelector::Id(client_id) => Some(*client_id),
+            _ => None,
+        }
+    }
+}
+
+impl ClientGroup {
+    fn matches(&self, client: &ClientIdentity) -> bool {
+        let included = (self.ids.is_empty() && self.names.is_empty() && self.tags.is_empty())
+            || Self::matches_any(&self.ids, &self.names, &self.tags, client);
+        included
+            && !Self::matches_any(
+                &self.exclude_ids,
+                &self.exclude_names,
+                &self.exclude_tags,
+                client,
+            )
+    }
+
+    fn matches_any(
+        ids: &[usize],
+        names: &[String],
+        tags: &[String],
+        client: &ClientIdentity,
+    ) -> bool {
+        ids.contains(&client.id)
+            || client
+                .name
+                .is_some_and(|client_name| names.iter().any(|name| name == client_name))
+            || tags.iter().any(|tag| client.tags.contains(tag))
+    }
+}
+
+/// Tags are short identifiers like `left-wall` without whitespace or commas.
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    if tag.is_empty() {
+        Err(String::from("Tag must not be empty."))
+    } else if tag.chars().any(|c| c.is_whitespace() || c == ',') {
+        Err(format!(
+            "Tag '{tag}' must not contain whitespace or commas."
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Trims and validates the tags, duplicates are removed.
+pub fn normalize_tags(
+    tags: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<BTreeSet<String>, String> {
+    tags.into_iter()
+        .map(|tag| {
+            let tag = tag.as_ref().trim();
+            validate_tag(tag).map(|()| tag.to_string())
+        })
+        .collect()
+}
+
+/// Parses a comma separated list of tags as used in URLs, empty entries are skipped.
+pub fn parse_tags(value: &str) -> Result<BTreeSet<String>, String> {
+    normalize_tags(value.split(',').filter(|tag| !tag.trim().is_empty()))
+}
+
+/// Selects instances by their names or by glob patterns like `lower-*`. Names are selected even
+/// if the instance is not known yet, patterns only match known instances.
+#[derive(Debug)]
+pub struct InstanceSelector {
+    names: BTreeSet<String>,
+    patterns: GlobSet,
+}
+
+impl InstanceSelector {
+    /// Parses a comma separated list of instance names and patterns.
+    pub fn parse(value: &str) -> Result<InstanceSelector, String> {
+        let mut names = BTreeSet::new();
+        let mut patterns = GlobSetBuilder::new();
+        for entry in value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            if entry.contains(['*', '?', '[', '{']) {
+                let glob = Glob::new(entry)
+                    .map_err(|err| format!("Invalid instance pattern '{entry}': {err}"))?;
+                patterns.add(glob);
+            } else {
+                names.insert(entry.to_string());
+            }
+        }
+        let patterns = patterns
+            .build()
+            .map_err(|err| format!("Invalid instance patterns: {err}"))?;
+        if names.is_empty() && patterns.is_empty() {
+            return Err(String::from("At least one instance must be given."));
+        }
+        Ok(InstanceSelector { names, patterns })
+    }
+
+    /// Returns the selected instances among the known ones, sorted by name.
+    pub fn resolve<'a>(&self, known_instances: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let mut instances = self.names.clone();
+        instances.extend(
+            known_instances
+                .into_iter()
+                .filter(|instance| self.patterns.is_match(instance))
+                .map(String::from),
+        );
+        instances.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity<'a>(
+        id: usize,
+        name: Option<&'a str>,
+        tags: &'a BTreeSet<String>,
+    ) -> ClientIdentity<'a> {
+        ClientIdentity { id, name, tags }
+    }
+
+    #[test]
+    fn test_deserialize_selector() {
+        assert_eq!(
+            serde_json::from_str::<ClientSelector>("3").unwrap(),
+            ClientSelector::Id(3)
+        );
+        assert_eq!(
+            serde_json::from_str::<ClientSelector>(r#""program-left""#).unwrap(),
+            ClientSelector::Name(String::from("program-left"))
+        );
+        assert_eq!(
+            serde_json::from_str::<ClientSelector>(r#"{"tags": ["left-wall"], "excludeIds": [2]}"#)
+                .unwrap(),
+            ClientSelector::Group(ClientGroup {
+                tags: vec![String::from("left-wall")],
+                exclude_ids: vec![2],
+                ..ClientGroup::default()
+            })
+        );
+        assert!(serde_json::from_str::<ClientSelector>(r#"{"tag": "left-wall"}"#).is_err());
+    }
+
+    #[test]
+    fn test_match_group() {
+        let left_wall = BTreeSet::from([String::from("left-wall")]);
+        let preview = BTreeSet::from([String::from("preview"), String::from("left-wall")]);
+        let no_tags = BTreeSet::new();
+        let clients = [
+            identity(0, Some("program-left"), &left_wall),
+            identity(1, Some("preview-left"), &preview),
+            identity(2, None, &no_tags),
+        ];
+        let selected = |group: ClientGroup| -> Vec<usize> {
+            let selector = ClientSelector::Group(group);
+            clients
+                .iter()
+                .filter(|client| selector.matches_identity(client))
+                .map(|client| client.id)
+                .collect()
+        };
+
+        assert_eq!(selected(ClientGroup::default()), vec![0, 1, 2]);
+        assert_eq!(
+            selected(ClientGroup {
+                tags: vec![String::from("left-wall")],
+                ..ClientGroup::default()
+            }),
+            vec![0, 1]
+        );
+        assert_eq!(
+            selected(ClientGroup {
+                tags: vec![String::from("left-wall")],
+                exclude_tags: vec![String::from("preview")],
+                ..ClientGroup::default()
+            }),
+            vec![0]
+        );
+        assert_eq!(
+            selected(ClientGroup {
+                ids: vec![2],
+                names: vec![String::from("preview-left")],
+                ..ClientGroup::default()
+            }),
+            vec![1, 2]
+        );
+        assert_eq!(
+            selected(ClientGroup {
+                exclude_names: vec![String::from("program-left")],
+                exclude_ids: vec![2],
+                ..ClientGroup::default()
+            }),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn test_validate_tag() {
+        assert!(validate_tag("left-wall").is_ok());
+        assert!(validate_tag("").is_err());
+        assert!(validate_tag("left wall").is_err());
+        assert!(validate_tag("a,b").is_err());
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(" left-wall,,program ,left-wall").unwrap(),
+            BTreeSet::from([String::from("left-wall"), String::from("program")])
+        );
+        assert!(parse_tags("").unwrap().is_empty());
+        assert!(parse_tags("left wall").is_err());
+    }
+
+    #[test]
+    fn test_resolve_instances() {
+        let known = ["lower-left", "lower-right", "main", "scoreboard"];
+        let selector = InstanceSelector::parse("lower-*, scoreboard, sponsor").unwrap();
+        assert_eq!(
+            selector.resolve(known),
+            vec!["lower-left", "lower-right", "scoreboard", "sponsor"]
+        );
+        assert_eq!(
+            InstanceSelector::parse("*").unwrap().resolve(known),
+            known.to_vec()
+        );
+        assert!(InstanceSelector::parse(" , ").is_err());
+        assert!(InstanceSelector::parse("lower-[").is_err());
+    }
+}
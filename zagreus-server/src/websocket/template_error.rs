@@ -0,0 +1,193 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+
+/// An error a template reported. Errors that a client reports repeatedly are counted instead of
+/// stored again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateError {
+    pub client_id: usize,
+    pub message: String,
+    pub stack: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub count: u64,
+}
+
+/// Number of clients whose errors are kept per instance. Clients get a new id when they
+/// reconnect, so without a limit the errors of long gone clients would pile up.
+const MAX_CLIENTS_WITH_ERRORS: usize = 64;
+
+/// The most recent template errors of the clients of an instance. Every client has its own
+/// bounded log, so a client that reports many errors cannot push out the errors of the others.
+/// When the log of a client is full, its error that was not reported for the longest time is
+/// dropped.
+pub struct TemplateErrorLog {
+    capacity: usize,
+    /// Errors per client, ordered by when they were last reported.
+    errors: HashMap<usize, VecDeque<RecordedError>>,
+    next_sequence: u64,
+}
+
+/// Orders the errors of different clients by when they were last reported.
+struct RecordedError {
+    sequence: u64,
+    error: TemplateError,
+}
+
+impl TemplateErrorLog {
+    pub fn new(capacity: usize) -> TemplateErrorLog {
+        TemplateErrorLog {
+            capacity,
+            errors: HashMap::new(),
+            next_sequence: 0,
+        }
+    }
+
+    pub fn record(&mut self, client_id: usize, message: String, stack: String, now: DateTime<Utc>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if !self.errors.contains_key(&client_id) && self.errors.len() == MAX_CLIENTS_WITH_ERRORS {
+            self.drop_least_recent_client();
+        }
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let client_errors = self.errors.entry(client_id).or_default();
+        let existing_index = client_errors.iter().position(|recorded| {
+            recorded.error.message == message && recorded.error.stack == stack
+        });
+        let error = match existing_index.and_then(|index| client_errors.remove(index)) {
+            Some(RecordedError { mut error, .. }) => {
+                error.last_seen = now;
+                error.count += 1;
+                error
+            }
+            None => TemplateError {
+                client_id,
+                message,
+                stack,
+                first_seen: now,
+                last_seen: now,
+                count: 1,
+            },
+        };
+        if client_errors.len() == self.capacity {
+            client_errors.pop_front();
+        }
+        client_errors.push_back(RecordedError { sequence, error });
+    }
+
+    /// Returns the errors of all clients, the most recently reported error last.
+    pub fn get_errors(&self) -> impl Iterator<Item = &TemplateError> {
+        let mut recorded_errors: Vec<&RecordedError> = self.errors.values().flatten().collect();
+        recorded_errors.sort_by_key(|recorded| recorded.sequence);
+        recorded_errors.into_iter().map(|recorded| &recorded.error)
+    }
+
+    /// Removes the errors of the given client or all errors.
+    pub fn clear(&mut self, client_id: Option<usize>) {
+        match client_id {
+            Some(client_id) => {
+                self.errors.remove(&client_id);
+            }
+            None => self.errors.clear(),
+        }
+    }
+
+    fn drop_least_recent_client(&mut self) {
+        let least_recent_client = self
+            .errors
+            .iter()
+            .filter_map(|(client_id, client_errors)| {
+                client_errors
+                    .back()
+                    .map(|recorded| (recorded.sequence, *client_id))
+            })
+            .min()
+            .map(|(_, client_id)| client_id);
+        if let Some(client_id) = least_recent_client {
+            self.errors.remove(&client_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(log: &TemplateErrorLog) -> Vec<(usize, &str, u64)> {
+        log.get_errors()
+            .map(|error| (error.client_id, error.message.as_str(), error.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_deduplicate_errors() {
+        let mut log = TemplateErrorLog::new(10);
+        let start = Utc::now();
+        log.record(1, String::from("a"), String::from("stack"), start);
+        log.record(1, String::from("b"), String::from("stack"), start);
+        log.record(2, String::from("a"), String::from("stack"), start);
+        let later = start + chrono::Duration::seconds(5);
+        log.record(1, String::from("a"), String::from("stack"), later);
+
+        assert_eq!(messages(&log), vec![(1, "b", 1), (2, "a", 1), (1, "a", 2)]);
+        let repeated = log.get_errors().last().unwrap();
+        assert_eq!(repeated.first_seen, start);
+        assert_eq!(repeated.last_seen, later);
+    }
+
+    #[test]
+    fn test_drop_oldest_error_when_full() {
+        let mut log = TemplateErrorLog::new(2);
+        let now = Utc::now();
+        log.record(1, String::from("a"), String::new(), now);
+        log.record(1, String::from("b"), String::new(), now);
+        log.record(1, String::from("a"), String::new(), now);
+        log.record(1, String::from("c"), String::new(), now);
+
+        assert_eq!(messages(&log), vec![(1, "a", 2), (1, "c", 1)]);
+    }
+
+    #[test]
+    fn test_keep_errors_of_other_clients_when_full() {
+        let mut log = TemplateErrorLog::new(2);
+        let now = Utc::now();
+        log.record(2, String::from("quiet"), String::new(), now);
+        for message in ["a", "b", "c", "d"] {
+            log.record(1, String::from(message), String::new(), now);
+        }
+
+        assert_eq!(
+            messages(&log),
+            vec![(2, "quiet", 1), (1, "c", 1), (1, "d", 1)]
+        );
+    }
+
+    #[test]
+    fn test_drop_least_recent_client() {
+        let mut log = TemplateErrorLog::new(1);
+        let now = Utc::now();
+        for client_id in 0..=MAX_CLIENTS_WITH_ERRORS {
+            log.record(client_id, String::from("a"), String::new(), now);
+        }
+
+        assert_eq!(MAX_CLIENTS_WITH_ERRORS, log.get_errors().count());
+        assert_eq!(1, log.get_errors().next().unwrap().client_id);
+    }
+
+    #[test]
+    fn test_clear_errors() {
+        let mut log = TemplateErrorLog::new(10);
+        let now = Utc::now();
+        log.record(1, String::from("a"), String::new(), now);
+        log.record(2, String::from("b"), String::new(), now);
+
+        log.clear(Some(1));
+        assert_eq!(messages(&log), vec![(2, "b", 1)]);
+        log.clear(None);
+        assert!(messages(&log).is_empty());
+    }
+}